@@ -0,0 +1,789 @@
+// src/app_registry.rs
+// Реестр поддерживаемых приложений: встроенный allowed_processes.json плюс apps.json
+// пользователя в папке конфигурации (рядом с config.json). Запись пользователя заменяет
+// встроенную с тем же process_name — так добавляют Blender или Houdini и правят app_id
+// без пересборки. Сопоставление окон (windows_api), команда get_allowed_processes
+// и движок активности читают один общий реестр. Команды add/update/remove_app
+// проверяют запись по полям и атомарно переписывают apps.json.
+use crate::discord_rpc::AllowedProcess;
+use crate::error::{ErrorCode, RpcError};
+use crate::presence_engine::PresenceEngine;
+use crate::presence_template;
+use crate::window_provider;
+use crate::windows_api::{self, TitleMatch};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::io::Write;
+use percent_encoding::percent_decode_str;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, State};
+
+pub const USER_FILE: &str = "apps.json";
+/// Схема, по которой фронтенд загружает иконки: convertFileSrc(icon_path, ICON_SCHEME)
+pub const ICON_SCHEME: &str = "jrpce-icon";
+
+static BUNDLED: Lazy<Vec<AllowedProcess>> = Lazy::new(|| {
+    let json = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/allowed_processes.json"
+    ));
+    serde_json::from_str(json).unwrap_or_else(|e| {
+        eprintln!("Failed to parse allowed_processes.json: {}", e);
+        Vec::new()
+    })
+});
+
+static REGISTRY: Lazy<RwLock<Arc<AppRegistry>>> =
+    Lazy::new(|| RwLock::new(Arc::new(AppRegistry::new(Vec::new(), None))));
+
+/// Длина snowflake-идентификатора Discord в десятичной записи
+const SNOWFLAKE_DIGITS: std::ops::RangeInclusive<usize> = 17..=20;
+
+/// Приложение из реестра с уже скомпилированными шаблонами заголовка
+#[derive(Debug)]
+pub struct RegisteredApp {
+    pub config: AllowedProcess,
+    pub title_extract_regexes: Vec<Regex>,
+}
+
+impl RegisteredApp {
+    fn new(config: AllowedProcess) -> Self {
+        let title_extract_regexes = config
+            .title_extract_patterns
+            .iter()
+            .flatten()
+            .filter_map(|p| {
+                Regex::new(p)
+                    .map_err(|e| {
+                        eprintln!("Invalid title pattern for {}: {}", config.process_name, e)
+                    })
+                    .ok()
+            })
+            .collect();
+        Self {
+            config,
+            title_extract_regexes,
+        }
+    }
+}
+
+/// Откуда запись: встроенная, из apps.json или встроенная, заменённая пользователем
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppSource {
+    Bundled,
+    User,
+    Override,
+}
+
+/// Результат test_title_patterns для одного заголовка
+#[derive(Debug, Clone, Serialize)]
+pub struct TitleTest {
+    pub title: String,
+    /// Процесс окна, если заголовок взят из открытого сейчас окна
+    pub process_name: Option<String>,
+    /// None — ни один шаблон не сработал, имя документа будет пустым
+    pub matched: Option<TitleMatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppEntry {
+    #[serde(flatten)]
+    pub app: AllowedProcess,
+    pub source: AppSource,
+}
+
+/// Ошибка проверки одного поля; `field` — имя поля, для шаблонов с индексом
+/// ("title_extract_patterns[1]")
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    #[serde(flatten)]
+    pub kind: FieldErrorKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum FieldErrorKind {
+    Required,
+    InvalidRegex {
+        reason: String,
+    },
+    /// app_id — не число из 17–20 цифр
+    InvalidSnowflake {
+        value: String,
+    },
+    /// Иконки нет среди ресурсов фронтенда
+    IconNotFound {
+        path: String,
+    },
+    /// Ошибка в шаблоне активности (незакрытая скобка, неверное имя переменной)
+    InvalidTemplate {
+        reason: String,
+    },
+    /// add_app для процесса, который уже есть в реестре
+    AlreadyExists,
+    /// remove_app для встроенного приложения без замены
+    Bundled,
+}
+
+impl FieldError {
+    fn new(field: impl Into<String>, kind: FieldErrorKind) -> Self {
+        Self {
+            field: field.into(),
+            kind,
+        }
+    }
+}
+
+fn invalid(errors: Vec<FieldError>) -> RpcError {
+    let fields = errors
+        .iter()
+        .map(|e| e.field.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    RpcError::new(
+        ErrorCode::InvalidApp,
+        format!("Invalid app definition: {}", fields),
+    )
+    .with_details(serde_json::json!({ "fields": errors }))
+}
+
+fn not_found(process_name: &str) -> RpcError {
+    RpcError::new(
+        ErrorCode::AppNotFound,
+        format!("No app registered for process {}", process_name),
+    )
+}
+
+/// Проверить запись по полям; `icon_exists` ищет иконку (в приложении — read_icon)
+pub fn validate(app: &AllowedProcess, icon_exists: impl Fn(&str) -> bool) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if app.process_name.trim().is_empty() {
+        errors.push(FieldError::new("process_name", FieldErrorKind::Required));
+    }
+    if app.display_name.trim().is_empty() {
+        errors.push(FieldError::new("display_name", FieldErrorKind::Required));
+    }
+    if app.icon_path.trim().is_empty() {
+        errors.push(FieldError::new("icon_path", FieldErrorKind::Required));
+    } else if !icon_exists(&app.icon_path) {
+        errors.push(FieldError::new(
+            "icon_path",
+            FieldErrorKind::IconNotFound {
+                path: app.icon_path.clone(),
+            },
+        ));
+    }
+    if let Some(app_id) = &app.app_id {
+        let is_snowflake = SNOWFLAKE_DIGITS.contains(&app_id.len())
+            && app_id.bytes().all(|b| b.is_ascii_digit())
+            && app_id.parse::<u64>().is_ok();
+        if !is_snowflake {
+            errors.push(FieldError::new(
+                "app_id",
+                FieldErrorKind::InvalidSnowflake {
+                    value: app_id.clone(),
+                },
+            ));
+        }
+    }
+    for (name, template) in app.presence.iter().flat_map(|p| p.fields()) {
+        if let Some(Err(e)) = template.map(presence_template::validate) {
+            errors.push(FieldError::new(
+                format!("presence.{}", name),
+                FieldErrorKind::InvalidTemplate {
+                    reason: e.to_string(),
+                },
+            ));
+        }
+    }
+    let patterns = app.title_extract_patterns.as_deref().unwrap_or_default();
+    errors.extend(compile_patterns("title_extract_patterns", patterns).1);
+    errors
+}
+
+/// Скомпилировать шаблоны заголовка; на каждый неверный шаблон — ошибка с индексом в `field`
+fn compile_patterns(field: &str, patterns: &[String]) -> (Vec<Regex>, Vec<FieldError>) {
+    let mut regexes = Vec::new();
+    let mut errors = Vec::new();
+    for (i, pattern) in patterns.iter().enumerate() {
+        match Regex::new(pattern) {
+            Ok(re) => regexes.push(re),
+            Err(e) => errors.push(FieldError::new(
+                format!("{}[{}]", field, i),
+                FieldErrorKind::InvalidRegex {
+                    reason: e.to_string(),
+                },
+            )),
+        }
+    }
+    (regexes, errors)
+}
+
+#[derive(Debug)]
+pub struct AppRegistry {
+    /// Встроенные приложения с учётом замен, затем новые приложения пользователя
+    apps: Vec<RegisteredApp>,
+    /// Записи из apps.json как есть
+    user: Vec<AllowedProcess>,
+    /// None — папка конфигурации неизвестна, изменения сохранить некуда
+    user_path: Option<PathBuf>,
+    /// apps.json не прочитался — не перезаписываем его, пока пользователь не исправит файл
+    load_error: Option<String>,
+}
+
+impl AppRegistry {
+    fn new(user: Vec<AllowedProcess>, user_path: Option<PathBuf>) -> Self {
+        let same = |a: &AllowedProcess, b: &AllowedProcess| {
+            a.process_name.eq_ignore_ascii_case(&b.process_name)
+        };
+        let bundled = BUNDLED
+            .iter()
+            .map(|b| user.iter().find(|u| same(u, b)).unwrap_or(b));
+        let added = user.iter().filter(|u| !BUNDLED.iter().any(|b| same(u, b)));
+        let apps = bundled
+            .chain(added)
+            .cloned()
+            .map(RegisteredApp::new)
+            .collect();
+        Self {
+            apps,
+            user,
+            user_path,
+            load_error: None,
+        }
+    }
+
+    /// Встроенный список и apps.json из `path`; нет файла — только встроенный
+    pub fn load(path: &Path) -> Self {
+        let user = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.to_string()),
+        };
+        match user {
+            Ok(user) => Self::new(user, Some(path.to_path_buf())),
+            Err(e) => {
+                let message = format!("Failed to load {}: {}", path.display(), e);
+                eprintln!("{}", message);
+                let mut registry = Self::new(Vec::new(), Some(path.to_path_buf()));
+                registry.load_error = Some(message);
+                registry
+            }
+        }
+    }
+
+    /// Приложение по имени процесса (без учёта регистра, как имена файлов в Windows)
+    pub fn find(&self, process_name: &str) -> Option<&RegisteredApp> {
+        self.apps
+            .iter()
+            .find(|app| app.config.process_name.eq_ignore_ascii_case(process_name))
+    }
+
+    pub fn app_id(&self, process_name: &str) -> Option<String> {
+        self.find(process_name)?.config.app_id.clone()
+    }
+
+    pub fn processes(&self) -> Vec<AllowedProcess> {
+        self.apps.iter().map(|app| app.config.clone()).collect()
+    }
+
+    pub fn entries(&self) -> Vec<AppEntry> {
+        self.apps
+            .iter()
+            .map(|app| {
+                let name = &app.config.process_name;
+                let bundled = BUNDLED
+                    .iter()
+                    .any(|b| b.process_name.eq_ignore_ascii_case(name));
+                let user = self
+                    .user
+                    .iter()
+                    .any(|u| u.process_name.eq_ignore_ascii_case(name));
+                let source = match (bundled, user) {
+                    (true, true) => AppSource::Override,
+                    (false, _) => AppSource::User,
+                    (true, false) => AppSource::Bundled,
+                };
+                AppEntry {
+                    app: app.config.clone(),
+                    source,
+                }
+            })
+            .collect()
+    }
+
+    /// Реестр с изменёнными записями пользователя; apps.json к этому моменту уже переписан
+    fn with_change(
+        &self,
+        change: impl FnOnce(&AppRegistry, &mut Vec<AllowedProcess>) -> Result<(), RpcError>,
+    ) -> Result<AppRegistry, RpcError> {
+        let path = self
+            .user_path
+            .clone()
+            .ok_or_else(|| RpcError::internal("App config dir is unavailable"))?;
+        if let Some(e) = &self.load_error {
+            return Err(RpcError::internal(e.clone()));
+        }
+        let mut user = self.user.clone();
+        change(self, &mut user)?;
+        write_atomic(&path, &user)
+            .map_err(|e| RpcError::internal(format!("Failed to write {}: {}", path.display(), e)))?;
+        Ok(AppRegistry::new(user, Some(path)))
+    }
+}
+
+/// Записать apps.json через временный файл и rename, чтобы сбой не оставил файл наполовину
+fn write_atomic(path: &Path, apps: &[AllowedProcess]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_vec_pretty(apps)?;
+    let tmp = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(&json)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+/// Изменить записи пользователя, сохранить apps.json и подменить общий реестр.
+/// Блокировка держится до подмены, чтобы параллельные изменения не потеряли друг друга.
+fn modify(
+    change: impl FnOnce(&AppRegistry, &mut Vec<AllowedProcess>) -> Result<(), RpcError>,
+) -> Result<(), RpcError> {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let updated = registry.with_change(change)?;
+    *registry = Arc::new(updated);
+    Ok(())
+}
+
+/// add_app: процесс, уже известный реестру (встроенный или свой), меняют через update_app
+fn add_user_app(
+    registry: &AppRegistry,
+    user: &mut Vec<AllowedProcess>,
+    app: AllowedProcess,
+) -> Result<(), RpcError> {
+    if registry.find(&app.process_name).is_some() {
+        return Err(invalid(vec![FieldError::new(
+            "process_name",
+            FieldErrorKind::AlreadyExists,
+        )]));
+    }
+    user.push(app);
+    Ok(())
+}
+
+/// remove_app: встроенное приложение без замены удалить нельзя
+fn remove_user_app(
+    registry: &AppRegistry,
+    user: &mut Vec<AllowedProcess>,
+    process_name: &str,
+) -> Result<(), RpcError> {
+    let before = user.len();
+    user.retain(|u| !u.process_name.eq_ignore_ascii_case(process_name));
+    if user.len() < before {
+        Ok(())
+    } else if registry.find(process_name).is_some() {
+        Err(invalid(vec![FieldError::new(
+            "process_name",
+            FieldErrorKind::Bundled,
+        )]))
+    } else {
+        Err(not_found(process_name))
+    }
+}
+
+fn check(app: &AllowedProcess, handle: &AppHandle) -> Result<(), RpcError> {
+    let errors = validate(app, |path| read_icon(handle, path).is_some());
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid(errors))
+    }
+}
+
+fn icon_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => return None,
+    })
+}
+
+/// Иконка по icon_path: сначала папка конфигурации (свои иконки кладут рядом с apps.json),
+/// затем ресурсы фронтенда. В dev ресурсы отдаёт dev-сервер, а не asset_resolver,
+/// поэтому там они читаются прямо из public/.
+pub fn read_icon(handle: &AppHandle, icon_path: &str) -> Option<(Vec<u8>, String)> {
+    let relative = Path::new(icon_path.trim_start_matches('/'));
+    // Только картинки и только внутри папки — по ICON_SCHEME не должны читаться apps.json и т.п.
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let mime = icon_mime(relative)?;
+    let read_from = |dir: PathBuf| {
+        std::fs::read(dir.join(relative))
+            .ok()
+            .map(|bytes| (bytes, mime.to_string()))
+    };
+
+    if let Some(icon) = handle.path().app_config_dir().ok().and_then(read_from) {
+        return Some(icon);
+    }
+    // неизвестный путь резолвер отдаёт как index.html, поэтому смотрим на тип
+    if let Some(asset) = handle.asset_resolver().get(icon_path.to_string()) {
+        if asset.mime_type().starts_with("image/") {
+            return Some((asset.bytes, asset.mime_type));
+        }
+    }
+    if tauri::is_dev() {
+        return read_from(Path::new(env!("CARGO_MANIFEST_DIR")).join("../public"));
+    }
+    None
+}
+
+/// Обработчик ICON_SCHEME: путь запроса — icon_path из реестра
+pub fn icon_response(handle: &AppHandle, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let icon_path = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    match read_icon(handle, &icon_path) {
+        Some((bytes, mime)) => Response::builder()
+            .header(header::CONTENT_TYPE, mime)
+            .body(bytes),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Vec::new()),
+    }
+    .unwrap_or_default()
+}
+
+/// Движок держит сопоставленные окна — перечитываем, чтобы новые приложения появились сразу
+async fn refresh(engine: &PresenceEngine) {
+    if let Err(e) = engine.refresh().await {
+        eprintln!("{}", e.context("Refresh after app registry change"));
+    }
+}
+
+/// Загрузить реестр из папки конфигурации приложения (вызывается в setup)
+pub fn init(config_dir: &Path) {
+    let registry = AppRegistry::load(&config_dir.join(USER_FILE));
+    *REGISTRY.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(registry);
+}
+
+/// Снимок реестра; до init — только встроенные приложения
+pub fn current() -> Arc<AppRegistry> {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[tauri::command]
+pub fn list_apps() -> Vec<AppEntry> {
+    current().entries()
+}
+
+/// Новое приложение; для уже известного процесса — update_app
+#[tauri::command]
+pub async fn add_app(
+    handle: AppHandle,
+    engine: State<'_, PresenceEngine>,
+    app: AllowedProcess,
+) -> Result<Vec<AppEntry>, RpcError> {
+    check(&app, &handle)?;
+    modify(|registry, user| add_user_app(registry, user, app))?;
+    refresh(&engine).await;
+    Ok(list_apps())
+}
+
+/// Заменить запись по process_name; встроенное приложение получает замену в apps.json
+#[tauri::command]
+pub async fn update_app(
+    handle: AppHandle,
+    engine: State<'_, PresenceEngine>,
+    app: AllowedProcess,
+) -> Result<Vec<AppEntry>, RpcError> {
+    check(&app, &handle)?;
+    modify(|registry, user| {
+        if registry.find(&app.process_name).is_none() {
+            return Err(not_found(&app.process_name));
+        }
+        let same = |u: &AllowedProcess| u.process_name.eq_ignore_ascii_case(&app.process_name);
+        match user.iter_mut().find(|u| same(u)) {
+            Some(existing) => *existing = app,
+            None => user.push(app),
+        }
+        Ok(())
+    })?;
+    refresh(&engine).await;
+    Ok(list_apps())
+}
+
+/// Удалить запись пользователя; для заменённого встроенного приложения возвращается встроенная
+#[tauri::command]
+pub async fn remove_app(
+    engine: State<'_, PresenceEngine>,
+    process_name: String,
+) -> Result<Vec<AppEntry>, RpcError> {
+    modify(|registry, user| remove_user_app(registry, user, &process_name))?;
+    refresh(&engine).await;
+    Ok(list_apps())
+}
+
+/// Прогнать шаблоны по заголовкам так же, как при сопоставлении окон (windows_api::match_title).
+/// `include_open_windows` добавляет заголовки открытых сейчас окон — только процесса
+/// `process_name`, если он задан.
+#[tauri::command]
+pub async fn test_title_patterns(
+    patterns: Vec<String>,
+    sample_titles: Vec<String>,
+    include_open_windows: bool,
+    process_name: Option<String>,
+) -> Result<Vec<TitleTest>, RpcError> {
+    let (regexes, errors) = compile_patterns("patterns", &patterns);
+    if !errors.is_empty() {
+        return Err(invalid(errors));
+    }
+
+    let mut titles: Vec<(String, Option<String>)> = sample_titles
+        .into_iter()
+        .map(|title| (title, None))
+        .collect();
+    if include_open_windows {
+        let open = tokio::task::spawn_blocking(move || {
+            let provider = window_provider::system();
+            provider
+                .list_windows()
+                .into_iter()
+                .filter_map(|w| {
+                    let name = w
+                        .pid
+                        .and_then(|pid| provider.process_info(pid))
+                        .map(|p| p.name)
+                        .unwrap_or_default();
+                    let wanted = process_name
+                        .as_deref()
+                        .is_none_or(|p| p.eq_ignore_ascii_case(&name));
+                    wanted.then_some((w.title, Some(name)))
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| RpcError::internal(format!("Window listing failed: {}", e)))?;
+        titles.extend(open);
+    }
+
+    Ok(titles
+        .into_iter()
+        .map(|(title, process_name)| TitleTest {
+            matched: windows_api::match_title(&regexes, &title),
+            title,
+            process_name,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discord_rpc::tests::temp_dir;
+    use crate::presence_template::PresenceTemplate;
+    use serde_json::json;
+
+    fn app(process_name: &str, display_name: &str) -> AllowedProcess {
+        AllowedProcess {
+            process_name: process_name.to_string(),
+            icon_path: "icons/app.png".to_string(),
+            display_name: display_name.to_string(),
+            app_id: Some("1300000000000000001".to_string()),
+            title_extract_patterns: None,
+            presence: None,
+        }
+    }
+
+    /// apps.json с `apps` в отдельном каталоге теста
+    fn user_file(name: &str, apps: &[AllowedProcess]) -> PathBuf {
+        let path = temp_dir(name).join(USER_FILE);
+        std::fs::write(&path, serde_json::to_string(apps).unwrap()).unwrap();
+        path
+    }
+
+    fn errors_json(app: &AllowedProcess, icon_exists: bool) -> serde_json::Value {
+        serde_json::to_value(validate(app, |_| icon_exists)).unwrap()
+    }
+
+    fn names(registry: &AppRegistry) -> Vec<String> {
+        registry.processes().into_iter().map(|a| a.process_name).collect()
+    }
+
+    #[test]
+    fn missing_user_file_means_bundled_only() {
+        let registry = AppRegistry::load(&temp_dir("registry-missing").join(USER_FILE));
+        assert!(registry.load_error.is_none());
+        assert_eq!(names(&registry), vec!["Cinema 4D.exe", "AfterFX.exe"]);
+        assert!(registry.entries().iter().all(|e| e.source == AppSource::Bundled));
+    }
+
+    #[test]
+    fn user_entry_overrides_bundled_by_process_name() {
+        let path = user_file(
+            "registry-override",
+            &[app("blender.exe", "Blender"), app("cinema 4d.exe", "Cinema 4D (beta)")],
+        );
+        let registry = AppRegistry::load(&path);
+        assert!(registry.load_error.is_none());
+
+        // Замена встаёт на место встроенной записи, новые приложения — после встроенных
+        assert_eq!(names(&registry), vec!["cinema 4d.exe", "AfterFX.exe", "blender.exe"]);
+        let c4d = registry.find("Cinema 4D.exe").unwrap();
+        assert_eq!(c4d.config.display_name, "Cinema 4D (beta)");
+        assert_eq!(registry.app_id("CINEMA 4D.EXE").as_deref(), Some("1300000000000000001"));
+
+        let sources: Vec<AppSource> = registry.entries().iter().map(|e| e.source).collect();
+        assert_eq!(sources, vec![AppSource::Override, AppSource::Bundled, AppSource::User]);
+    }
+
+    #[test]
+    fn malformed_user_file_is_reported_and_not_overwritten() {
+        let path = temp_dir("registry-malformed").join(USER_FILE);
+        std::fs::write(&path, "[{ \"process_name\": ").unwrap();
+        let registry = AppRegistry::load(&path);
+
+        let error = registry.load_error.clone().unwrap();
+        assert!(error.starts_with("Failed to load"), "{}", error);
+        assert_eq!(names(&registry), vec!["Cinema 4D.exe", "AfterFX.exe"]);
+
+        let err = registry
+            .with_change(|_, user| {
+                user.push(app("blender.exe", "Blender"));
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Internal);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[{ \"process_name\": ");
+    }
+
+    #[test]
+    fn complete_app_passes_validation() {
+        let mut blender = app("blender.exe", "Blender");
+        blender.title_extract_patterns = Some(vec![r" - (?P<file>.+\.blend)".to_string()]);
+        blender.presence = Some(PresenceTemplate {
+            details: Some("{file|title}".to_string()),
+            ..PresenceTemplate::default()
+        });
+        assert_eq!(errors_json(&blender, true), json!([]));
+    }
+
+    #[test]
+    fn invalid_regex_is_reported_with_its_index() {
+        let mut blender = app("blender.exe", "Blender");
+        blender.title_extract_patterns = Some(vec![r"\[(.+)\]".to_string(), "(unclosed".to_string()]);
+        let errors = validate(&blender, |_| true);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "title_extract_patterns[1]");
+        assert!(matches!(errors[0].kind, FieldErrorKind::InvalidRegex { .. }));
+    }
+
+    #[test]
+    fn app_id_must_be_a_snowflake() {
+        for app_id in ["13000000000000000ab", "1234567890123456", "123456789012345678901"] {
+            let mut blender = app("blender.exe", "Blender");
+            blender.app_id = Some(app_id.to_string());
+            assert_eq!(
+                errors_json(&blender, true),
+                json!([{ "field": "app_id", "code": "invalid_snowflake", "value": app_id }])
+            );
+        }
+        let mut blender = app("blender.exe", "Blender");
+        blender.app_id = Some("12345678901234567".to_string());
+        assert_eq!(errors_json(&blender, true), json!([]));
+    }
+
+    #[test]
+    fn missing_icon_and_blank_fields() {
+        assert_eq!(
+            errors_json(&app("blender.exe", " "), false),
+            json!([
+                { "field": "display_name", "code": "required" },
+                { "field": "icon_path", "code": "icon_not_found", "path": "icons/app.png" }
+            ])
+        );
+    }
+
+    #[test]
+    fn bad_presence_template_names_the_field() {
+        let mut blender = app("blender.exe", "Blender");
+        blender.presence = Some(PresenceTemplate {
+            details: Some("Editing {file".to_string()),
+            ..PresenceTemplate::default()
+        });
+        let errors = validate(&blender, |_| true);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "presence.details");
+        assert!(matches!(errors[0].kind, FieldErrorKind::InvalidTemplate { .. }));
+    }
+
+    #[test]
+    fn write_atomic_round_trip_leaves_no_temp_file() {
+        let dir = temp_dir("registry-write").join("config");
+        let path = dir.join(USER_FILE);
+        let apps = vec![app("blender.exe", "Blender"), app("houdini.exe", "Houdini")];
+        write_atomic(&path, &apps).unwrap();
+
+        let registry = AppRegistry::load(&path);
+        assert!(registry.load_error.is_none());
+        assert_eq!(
+            serde_json::to_value(&registry.user).unwrap(),
+            serde_json::to_value(&apps).unwrap()
+        );
+        let files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, vec![USER_FILE]);
+    }
+
+    #[test]
+    fn add_and_remove_keep_bundled_apps() {
+        let path = user_file("registry-modify", &[app("blender.exe", "Blender")]);
+        let registry = AppRegistry::load(&path);
+        let field_code = |err: RpcError| err.details.unwrap()["fields"][0]["code"].clone();
+
+        for existing in ["cinema 4d.exe", "BLENDER.EXE"] {
+            let err = registry
+                .with_change(|r, user| add_user_app(r, user, app(existing, "Duplicate")))
+                .unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidApp);
+            assert_eq!(field_code(err), "already_exists");
+        }
+
+        let err = registry
+            .with_change(|r, user| remove_user_app(r, user, "AfterFX.exe"))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidApp);
+        assert_eq!(field_code(err), "bundled");
+        let err = registry
+            .with_change(|r, user| remove_user_app(r, user, "houdini.exe"))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::AppNotFound);
+
+        let added = registry
+            .with_change(|r, user| add_user_app(r, user, app("houdini.exe", "Houdini")))
+            .unwrap();
+        assert_eq!(
+            names(&added),
+            vec!["Cinema 4D.exe", "AfterFX.exe", "blender.exe", "houdini.exe"]
+        );
+        let removed = added
+            .with_change(|r, user| remove_user_app(r, user, "Blender.exe"))
+            .unwrap();
+        assert_eq!(names(&removed), vec!["Cinema 4D.exe", "AfterFX.exe", "houdini.exe"]);
+        // Изменения уже в apps.json
+        assert_eq!(names(&AppRegistry::load(&path)), names(&removed));
+    }
+}
@@ -0,0 +1,605 @@
+// src/discord_ipc.rs
+// Собственный клиент Discord IPC: поиск сокета/pipe, handshake и обмен кадрами
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+#[cfg(unix)]
+type IpcStream = tokio::net::UnixStream;
+#[cfg(windows)]
+type IpcStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// Версия протокола, которую ждёт Discord в handshake
+const IPC_VERSION: u32 = 1;
+/// Discord слушает discord-ipc-0 .. discord-ipc-9
+pub const IPC_SLOTS: u32 = 10;
+/// Предел длины кадра: ответы Discord — единицы килобайт, а длина приходит от собеседника
+const MAX_FRAME_LEN: u32 = 1024 * 1024;
+/// Сколько ждать ответа на команду: зависший Discord считаем потерянным соединением
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Сколько ждать READY после handshake: перезапускающийся Discord может принять соединение и молчать
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opcode'ы кадров Discord IPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Handshake = 0,
+    Frame = 1,
+    Close = 2,
+    Ping = 3,
+    Pong = 4,
+}
+
+impl Opcode {
+    pub fn from_u32(op: u32) -> Option<Self> {
+        match op {
+            0 => Some(Opcode::Handshake),
+            1 => Some(Opcode::Frame),
+            2 => Some(Opcode::Close),
+            3 => Some(Opcode::Ping),
+            4 => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IpcError {
+    /// Ни один discord-ipc-N не ответил
+    NotFound,
+    /// Команда отправлена до connect() или соединение уже закрыто
+    NotConnected,
+    Io(io::Error),
+    /// Discord закрыл соединение (CLOSE-кадр), например из-за неверного client_id
+    Closed { code: i64, message: String },
+    /// Discord вернул evt: ERROR на команду
+    Discord { code: i64, message: String },
+    Protocol(String),
+    /// Discord запущен, но ни один экземпляр не подходит под выбранный InstanceSelector
+    NoMatchingInstance(String),
+}
+
+impl fmt::Display for IpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpcError::NotFound => write!(f, "no Discord IPC endpoint found"),
+            IpcError::NotConnected => write!(f, "IPC client is not connected"),
+            IpcError::Io(e) => write!(f, "IPC I/O error: {}", e),
+            IpcError::Closed { code, message } => {
+                write!(f, "Discord closed the connection ({}): {}", code, message)
+            }
+            IpcError::Discord { code, message } => write!(f, "Discord error {}: {}", code, message),
+            IpcError::Protocol(msg) => write!(f, "IPC protocol error: {}", msg),
+            IpcError::NoMatchingInstance(wanted) => {
+                write!(f, "no running Discord instance matches {}", wanted)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+impl IpcError {
+    /// Ошибка означает, что соединения больше нет (в отличие от отказа Discord выполнить команду)
+    pub fn is_connection_lost(&self) -> bool {
+        matches!(
+            self,
+            IpcError::Io(_) | IpcError::Closed { .. } | IpcError::NotConnected
+        )
+    }
+}
+
+impl From<io::Error> for IpcError {
+    fn from(e: io::Error) -> Self {
+        IpcError::Io(e)
+    }
+}
+
+/// Каталоги, в которых Discord (обычный, Flatpak, Snap) создаёт свои сокеты
+#[cfg(unix)]
+fn socket_dirs() -> Vec<PathBuf> {
+    const ENV_KEYS: [&str; 4] = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"];
+    const SUBDIRS: [&str; 6] = [
+        "",
+        "app/com.discordapp.Discord",
+        "app/com.discordapp.DiscordCanary",
+        ".flatpak/com.discordapp.Discord/xdg-run",
+        "snap.discord",
+        "snap.discord-canary",
+    ];
+
+    let mut bases: Vec<PathBuf> = ENV_KEYS
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .collect();
+    bases.push(PathBuf::from("/tmp"));
+
+    let mut dirs = Vec::new();
+    for base in bases {
+        for sub in SUBDIRS {
+            let dir = base.join(sub);
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+/// Все пути, где может находиться discord-ipc-N на текущей платформе (по порядку приоритета)
+pub fn candidate_endpoints() -> Vec<PathBuf> {
+    #[cfg(unix)]
+    {
+        socket_dirs()
+            .into_iter()
+            .flat_map(|dir| (0..IPC_SLOTS).map(move |i| dir.join(format!("discord-ipc-{}", i))))
+            .collect()
+    }
+    #[cfg(windows)]
+    {
+        (0..IPC_SLOTS)
+            .map(|i| PathBuf::from(format!(r"\\.\pipe\discord-ipc-{}", i)))
+            .collect()
+    }
+}
+
+/// Кто-то принимает соединения на этом сокете/pipe (handshake не выполняется)
+#[cfg(any(test, feature = "mock-discord"))]
+pub async fn endpoint_alive(path: &Path) -> bool {
+    open_endpoint(path).await.is_ok()
+}
+
+/// Номер N из имени discord-ipc-N
+pub fn endpoint_slot(path: &Path) -> Option<u32> {
+    path.file_name()?.to_str()?.strip_prefix("discord-ipc-")?.parse().ok()
+}
+
+/// Результат пробного handshake с одним endpoint'ом
+pub struct Probe {
+    pub endpoint: PathBuf,
+    pub latency: Duration,
+    pub result: Result<ReadyData, IpcError>,
+}
+
+/// Подключиться к каждому существующему endpoint'у по очереди (в порядке перебора connect())
+/// и сразу закрыть соединение. Отсутствующие сокеты/pipe'ы пропускаются.
+pub async fn probe_endpoints(client_id: &str, timeout: Duration) -> Vec<Probe> {
+    let mut probes = Vec::new();
+    for path in candidate_endpoints() {
+        let mut client = IpcClient::new(client_id);
+        let started = Instant::now();
+        let result = match tokio::time::timeout(timeout, client.connect_endpoint(&path)).await {
+            Ok(Ok(())) => Ok(client.ready.clone().unwrap_or_default()),
+            Ok(Err(IpcError::Io(e))) if e.kind() == io::ErrorKind::NotFound => continue,
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(IpcError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "handshake timed out",
+            ))),
+        };
+        let latency = started.elapsed();
+        let _ = client.close().await;
+        probes.push(Probe {
+            endpoint: path,
+            latency,
+            result,
+        });
+    }
+    probes
+}
+
+async fn open_endpoint(path: &Path) -> io::Result<IpcStream> {
+    #[cfg(unix)]
+    {
+        tokio::net::UnixStream::connect(path).await
+    }
+    #[cfg(windows)]
+    {
+        tokio::net::windows::named_pipe::ClientOptions::new().open(path)
+    }
+}
+
+/// Discord не ответил вовремя — для воркера это то же, что обрыв соединения
+fn timed_out(message: String) -> IpcError {
+    IpcError::Io(io::Error::new(io::ErrorKind::TimedOut, message))
+}
+
+/// Уникальный nonce для сопоставления ответа с командой
+fn next_nonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!("{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Записать один кадр: [opcode: u32 LE][длина: u32 LE][JSON]
+pub async fn write_frame<W: AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    op: Opcode,
+    payload: &Value,
+) -> io::Result<()> {
+    let body = payload.to_string();
+    let mut buf = Vec::with_capacity(8 + body.len());
+    buf.extend_from_slice(&(op as u32).to_le_bytes());
+    buf.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    buf.extend_from_slice(body.as_bytes());
+    writer.write_all(&buf).await?;
+    writer.flush().await
+}
+
+/// Прочитать один кадр; opcode возвращается как есть, чтобы вызывающий решал, что с ним делать
+pub async fn read_frame<R: AsyncReadExt + Unpin>(reader: &mut R) -> Result<(u32, Value), IpcError> {
+    let op = reader.read_u32_le().await?;
+    let len = reader.read_u32_le().await?;
+    if len > MAX_FRAME_LEN {
+        return Err(IpcError::Protocol(format!(
+            "frame length {} exceeds {} bytes",
+            len, MAX_FRAME_LEN
+        )));
+    }
+    let mut body = vec![0u8; len as usize];
+    reader.read_exact(&mut body).await?;
+    let payload = serde_json::from_slice(&body)
+        .map_err(|e| IpcError::Protocol(format!("invalid JSON payload: {}", e)))?;
+    Ok((op, payload))
+}
+
+fn close_error(payload: &Value) -> IpcError {
+    IpcError::Closed {
+        code: payload["code"].as_i64().unwrap_or_default(),
+        message: payload["message"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// Ответ на команду или диспатч с evt: ERROR
+pub fn dispatch_error(payload: &Value) -> Option<IpcError> {
+    if payload["evt"] != "ERROR" {
+        return None;
+    }
+    Some(IpcError::Discord {
+        code: payload["data"]["code"].as_i64().unwrap_or_default(),
+        message: payload["data"]["message"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+/// Пользователь Discord из READY
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscordUser {
+    pub id: String,
+    pub username: String,
+    pub discriminator: String,
+    pub global_name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// Конфигурация клиента из READY (environment — production / staging и т.п.)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub cdn_host: String,
+    pub api_endpoint: String,
+    pub environment: String,
+}
+
+/// Сборка Discord, определённая по api_endpoint из READY
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscordBuild {
+    Stable,
+    Ptb,
+    Canary,
+    Unknown,
+}
+
+impl DiscordBuild {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscordBuild::Stable => "stable",
+            DiscordBuild::Ptb => "ptb",
+            DiscordBuild::Canary => "canary",
+            DiscordBuild::Unknown => "unknown",
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn build(&self) -> DiscordBuild {
+        // api_endpoint приходит без схемы: //discord.com/api, //ptb.discord.com/api, ...
+        let host = self
+            .api_endpoint
+            .trim_start_matches("https:")
+            .trim_start_matches("//");
+        if host.starts_with("ptb.") {
+            DiscordBuild::Ptb
+        } else if host.starts_with("canary.") {
+            DiscordBuild::Canary
+        } else if host.starts_with("discord.com") || host.starts_with("discordapp.com") {
+            DiscordBuild::Stable
+        } else {
+            DiscordBuild::Unknown
+        }
+    }
+}
+
+/// Какой экземпляр Discord выбрать, если их запущено несколько (например, stable и Canary)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InstanceSelector {
+    /// Первый, кто ответит на handshake
+    #[default]
+    Any,
+    /// Конкретный discord-ipc-N
+    Index { index: u32 },
+    /// Сборка по READY (api_endpoint): stable / ptb / canary
+    Build { build: DiscordBuild },
+}
+
+impl fmt::Display for InstanceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstanceSelector::Any => write!(f, "any instance"),
+            InstanceSelector::Index { index } => write!(f, "discord-ipc-{}", index),
+            InstanceSelector::Build { build } => write!(f, "Discord {}", build.as_str()),
+        }
+    }
+}
+
+impl InstanceSelector {
+    /// Подходит ли endpoint discord-ipc-`slot`, ответивший READY сборки `build`
+    pub fn matches(&self, slot: Option<u32>, build: DiscordBuild) -> bool {
+        match self {
+            InstanceSelector::Any => true,
+            InstanceSelector::Index { index } => slot == Some(*index),
+            InstanceSelector::Build { build: wanted } => build == *wanted,
+        }
+    }
+}
+
+/// `data` первого диспатча READY после handshake
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadyData {
+    pub v: u32,
+    pub config: ClientConfig,
+    pub user: DiscordUser,
+}
+
+type Frame = (u32, Value);
+
+/// Фоновое чтение кадров: всё прочитанное уходит в канал, ошибка чтения завершает задачу
+async fn read_loop(mut reader: ReadHalf<IpcStream>, tx: mpsc::UnboundedSender<Result<Frame, IpcError>>) {
+    loop {
+        let frame = read_frame(&mut reader).await;
+        let stop = frame.is_err();
+        if tx.send(frame).is_err() || stop {
+            break;
+        }
+    }
+}
+
+/// Клиент одного соединения с Discord
+pub struct IpcClient {
+    client_id: String,
+    writer: Option<WriteHalf<IpcStream>>,
+    /// Кадры от фоновой задачи чтения; чтение из канала можно безопасно отменять
+    incoming: Option<mpsc::UnboundedReceiver<Result<Frame, IpcError>>>,
+    reader: Option<JoinHandle<()>>,
+    endpoint: Option<PathBuf>,
+    ready: Option<ReadyData>,
+}
+
+impl IpcClient {
+    pub fn new(client_id: &str) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            writer: None,
+            incoming: None,
+            reader: None,
+            endpoint: None,
+            ready: None,
+        }
+    }
+
+    pub fn endpoint(&self) -> Option<&Path> {
+        self.endpoint.as_deref()
+    }
+
+    /// Пользователь и конфигурация из READY текущего соединения
+    pub fn ready(&self) -> Option<&ReadyData> {
+        self.ready.as_ref()
+    }
+
+    /// Перебрать все известные endpoint'ы и подключиться к первому, который примет handshake
+    /// и подойдёт под `instance`
+    pub async fn connect(&mut self, instance: &InstanceSelector) -> Result<(), IpcError> {
+        let mut last_err = IpcError::NotFound;
+        for path in candidate_endpoints() {
+            if let InstanceSelector::Index { index } = instance {
+                if endpoint_slot(&path) != Some(*index) {
+                    continue;
+                }
+            }
+            match self.connect_endpoint(&path).await {
+                Ok(()) => {
+                    let build = self.ready.as_ref().map(|r| r.config.build());
+                    match instance {
+                        InstanceSelector::Build { build: wanted } if build != Some(*wanted) => {
+                            // Это другой экземпляр Discord — вежливо отключаемся и ищем дальше
+                            let _ = self.close().await;
+                            last_err = IpcError::NoMatchingInstance(instance.to_string());
+                        }
+                        _ => return Ok(()),
+                    }
+                }
+                // Несуществующий сокет — обычное дело, ошибку о нём не запоминаем
+                Err(IpcError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => last_err = e,
+            }
+        }
+        // Нужного discord-ipc-N нет, хотя другие экземпляры могут быть запущены
+        if matches!(last_err, IpcError::NotFound) && *instance != InstanceSelector::Any {
+            last_err = IpcError::NoMatchingInstance(instance.to_string());
+        }
+        Err(last_err)
+    }
+
+    /// Подключиться к конкретному сокету/pipe и выполнить handshake
+    pub async fn connect_endpoint(&mut self, path: &Path) -> Result<(), IpcError> {
+        self.disconnect();
+
+        let mut stream = open_endpoint(path).await?;
+        let handshake = json!({ "v": IPC_VERSION, "client_id": self.client_id });
+        let reply = tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+            write_frame(&mut stream, Opcode::Handshake, &handshake).await?;
+            read_frame(&mut stream).await
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(timed_out(format!(
+                "no READY within {}s after handshake",
+                HANDSHAKE_TIMEOUT.as_secs()
+            )))
+        });
+        let (op, payload) = reply?;
+        match Opcode::from_u32(op) {
+            Some(Opcode::Frame) if payload["evt"] == "READY" => {
+                let (reader, writer) = tokio::io::split(stream);
+                let (tx, rx) = mpsc::unbounded_channel();
+                self.reader = Some(tokio::spawn(read_loop(reader, tx)));
+                self.incoming = Some(rx);
+                self.writer = Some(writer);
+                self.endpoint = Some(path.to_path_buf());
+                self.ready = serde_json::from_value(payload["data"].clone()).ok();
+                Ok(())
+            }
+            Some(Opcode::Close) => Err(close_error(&payload)),
+            _ => Err(IpcError::Protocol(format!(
+                "unexpected handshake reply (op {}): {}",
+                op, payload
+            ))),
+        }
+    }
+
+    /// Забыть соединение; задача чтения держит свою половину потока, поэтому её нужно остановить
+    fn disconnect(&mut self) {
+        self.writer = None;
+        self.ready = None;
+        self.endpoint = None;
+        self.incoming = None;
+        if let Some(reader) = self.reader.take() {
+            reader.abort();
+        }
+    }
+
+    /// Следующий входящий кадр. Отмена безопасна, поэтому метод можно ждать в select!
+    pub async fn next_frame(&mut self) -> Result<Frame, IpcError> {
+        let incoming = self.incoming.as_mut().ok_or(IpcError::NotConnected)?;
+        incoming.recv().await.unwrap_or(Err(IpcError::NotConnected))
+    }
+
+    /// Обработать кадр вне команды: PING → PONG, CLOSE → ошибка; диспатчи возвращаются как есть
+    pub async fn handle_frame(&mut self, frame: Result<Frame, IpcError>) -> Result<Option<Value>, IpcError> {
+        let result = match frame {
+            Ok((op, payload)) => match Opcode::from_u32(op) {
+                Some(Opcode::Ping) => self.write(Opcode::Pong, &payload).await.map(|_| None),
+                Some(Opcode::Close) => Err(close_error(&payload)),
+                Some(Opcode::Frame) => Ok(Some(payload)),
+                _ => Ok(None),
+            },
+            Err(e) => Err(e),
+        };
+        if result.as_ref().is_err_and(IpcError::is_connection_lost) {
+            self.disconnect();
+        }
+        result
+    }
+
+    async fn write(&mut self, op: Opcode, payload: &Value) -> Result<(), IpcError> {
+        let writer = self.writer.as_mut().ok_or(IpcError::NotConnected)?;
+        write_frame(writer, op, payload).await?;
+        Ok(())
+    }
+
+    /// Отправить команду и дождаться ответа с тем же nonce
+    pub async fn command(&mut self, cmd: &str, args: Value) -> Result<Value, IpcError> {
+        let nonce = next_nonce();
+        let request = json!({ "cmd": cmd, "args": args, "nonce": nonce });
+
+        let result = tokio::time::timeout(COMMAND_TIMEOUT, self.exchange(&request, &nonce))
+            .await
+            .unwrap_or_else(|_| {
+                Err(timed_out(format!(
+                    "no reply to {} within {}s",
+                    cmd,
+                    COMMAND_TIMEOUT.as_secs()
+                )))
+            });
+        if result.as_ref().is_err_and(IpcError::is_connection_lost) {
+            // Соединение мертво или Discord завис — больше им не пользуемся
+            self.disconnect();
+        }
+        result
+    }
+
+    async fn exchange(&mut self, request: &Value, nonce: &str) -> Result<Value, IpcError> {
+        self.write(Opcode::Frame, request).await?;
+
+        loop {
+            let frame = self.next_frame().await;
+            let Some(payload) = self.handle_frame(frame).await? else {
+                continue;
+            };
+            if payload["nonce"] != nonce {
+                // Посторонние диспатчи (подписки и т.п.) пропускаем
+                continue;
+            }
+            if let Some(e) = dispatch_error(&payload) {
+                return Err(e);
+            }
+            return Ok(payload);
+        }
+    }
+
+    /// `activity` — сериализованный `activity::Activity`; возвращает активность в том виде,
+    /// в каком её принял Discord
+    pub async fn set_activity(&mut self, activity: Value) -> Result<Value, IpcError> {
+        let args = json!({ "pid": std::process::id(), "activity": activity });
+        self.command("SET_ACTIVITY", args)
+            .await
+            .map(|mut reply| reply["data"].take())
+    }
+
+    pub async fn clear_activity(&mut self) -> Result<(), IpcError> {
+        let args = json!({ "pid": std::process::id(), "activity": Value::Null });
+        self.command("SET_ACTIVITY", args).await.map(|_| ())
+    }
+
+    /// Вежливо закрыть соединение (CLOSE-кадр) и забыть поток
+    pub async fn close(&mut self) -> Result<(), IpcError> {
+        let result = match self.writer.as_mut() {
+            Some(writer) => match write_frame(writer, Opcode::Close, &json!({})).await {
+                Ok(()) => writer.shutdown().await.map_err(IpcError::from),
+                Err(e) => Err(e.into()),
+            },
+            None => Ok(()),
+        };
+        self.disconnect();
+        result
+    }
+}
+
+impl Drop for IpcClient {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
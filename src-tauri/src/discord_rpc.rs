@@ -1,308 +1,171 @@
 // src/discord_rpc.rs
-use discord_rich_presence::{activity, DiscordIpcClient};
-use discord_rich_presence::DiscordIpc; // <- обязательно, чтобы методы трейта были в scope
-use std::sync::{Arc, Mutex};
-use std::{thread, time::Duration};
-use tauri::State;
-use std::fs;
-use std::process::Command;
+use crate::app_registry;
+use crate::discord_ipc::{self, DiscordBuild, DiscordUser, InstanceSelector};
+use crate::error::{ErrorCode, Hint, RpcError};
+use crate::presence::{Presence, PresenceBuilder};
+use crate::presence_template::PresenceTemplate;
+use crate::rpc_events;
+use crate::rpc_pool::RpcPool;
+use crate::rpc_worker::{BackoffConfig, RpcStatus};
+use tauri::{AppHandle, State};
+use std::sync::Arc;
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
 
 
 /// Состояние RPC для Tauri
 pub struct RpcState {
-    /// Arc<Mutex<DiscordIpcClient>> внутри Option, чтобы можно было дешёво клонировать хэндл для команд
-    pub discord: Mutex<Option<Arc<Mutex<DiscordIpcClient>>>>,
+    /// Воркеры по app_id, каждый владеет своим IPC-клиентом; команды только шлют им сообщения
+    pub pool: Arc<RpcPool>,
 }
 
 impl RpcState {
-    pub fn new() -> Self {
+    /// Воркеры шлют события (discord-rpc:*) во все окна приложения
+    pub fn new(app: AppHandle) -> Self {
         Self {
-            discord: Mutex::new(None),
+            pool: RpcPool::new(rpc_events::tauri_sink(app)),
         }
     }
 }
 
-/// Вспомогательная утилита для форматирования ошибок (Debug → String)
-fn err_to_string<E: std::fmt::Debug>(e: E) -> String {
-    format!("{:?}", e)
+/// `instance` — какой из запущенных Discord использовать (по умолчанию любой),
+/// `backoff` — параметры автоматического переподключения, если Discord пропадёт.
+/// `concurrent` — не закрывать соединения других приложений (своя активность у каждого),
+/// `pid` — процесс приложения: когда он завершится, соединение закроется само.
+#[tauri::command]
+pub async fn init_rpc(
+    state: State<'_, RpcState>,
+    app_id: String,
+    instance: Option<InstanceSelector>,
+    backoff: Option<BackoffConfig>,
+    pid: Option<u32>,
+    concurrent: Option<bool>,
+) -> Result<(), RpcError> {
+    state
+        .pool
+        .connect(
+            app_id,
+            instance.unwrap_or_default(),
+            backoff.unwrap_or_default(),
+            pid,
+            concurrent.unwrap_or(false),
+        )
+        .await?;
+    println!("Discord RPC initialized successfully.");
+    Ok(())
 }
 
+/// Текущее состояние соединения (Disconnected/Connecting/Connected/Backoff).
+/// Без `app_id` — соединение последнего init_rpc.
 #[tauri::command]
-pub fn init_rpc(state: State<'_, RpcState>, app_id: String) -> Result<(), String> {
-    let max_attempts = 6u32;
-
-    for attempt in 1..=max_attempts {
-        let mut client = DiscordIpcClient::new(&app_id);
-
-        match client.connect() {
-            Ok(_) => {
-                println!("Discord IPC connected on attempt {}/{}", attempt, max_attempts);
+pub fn get_rpc_status(state: State<'_, RpcState>, app_id: Option<String>) -> RpcStatus {
+    state.pool.status(app_id.as_deref())
+}
 
-                let arc = Arc::new(Mutex::new(client));
-                let mut guard = state.discord.lock().map_err(|e| format!("Mutex poisoned: {:?}", e))?;
-                *guard = Some(arc);
-                println!("Discord RPC initialized successfully.");
-                return Ok(());
-            }
-            Err(e) => {
-                let s = err_to_string(e);
-                eprintln!("Discord connect attempt {}/{} failed: {}", attempt, max_attempts, s);
-                if attempt < max_attempts {
-                    thread::sleep(Duration::from_millis(500));
-                    continue;
-                } else {
-                    return Err(format!(
-                        "Failed to init Discord IPC after {} attempts: {}\n\
-                         Проверьте, что:\n 1) Discord Desktop запущен (не Microsoft Store)\n 2) App ID верен\n 3) Discord полностью загрузился и не в состоянии обновления\n\
-                         Дополнительно: посмотрите pipe'ы `\\\\.\\pipe\\discord-ipc-*` и логи Discord.",
-                        max_attempts,
-                        s
-                    ));
-                }
-            }
-        }
-    }
+/// Состояния всех открытых соединений (режим concurrent)
+#[tauri::command]
+pub fn get_rpc_pool_status(state: State<'_, RpcState>) -> Vec<RpcStatus> {
+    state.pool.statuses()
+}
 
-    Err("Unreachable init_rpc error".into())
+/// Пользователь Discord из READY (после первого успешного init_rpc), для карточки профиля
+#[tauri::command]
+pub fn get_discord_user(state: State<'_, RpcState>) -> Option<DiscordUser> {
+    state.pool.user()
 }
 
-// ----------------- update_rpc (без блокировок main thread) -----------------
+/// Проверка, обрезка и сборка активности — в presence::PresenceBuilder
 #[tauri::command]
-pub fn update_rpc(
+pub async fn update_rpc(
     state: State<'_, RpcState>,
-    details: String,
-    state_text: String,
-    large_image: String,
-    small_image: String,
-    large_text: Option<String>,
-    small_text: Option<String>,
-    activity_type: Option<String>,
-) -> Result<(), String> {
-    // Получаем Arc<Mutex<DiscordIpcClient>>
-    let arc_client = {
-        let guard = state.discord.lock().map_err(|e| format!("Mutex poisoned: {:?}", e))?;
-        guard
-            .as_ref()
-            .ok_or_else(|| "RPC client not initialized. Call init_rpc first.".to_string())?
-            .clone()
-    };
-
-    // Развернём опции в owned строки (чтобы можно было безопасно перемещать в поток)
-    let large_text = large_text.unwrap_or_default();
-    let small_text = small_text.unwrap_or_default();
-
-    // Клонируем строки, которые понадобятся в фоновом потоке (owned)
-    let state_text_cl = state_text.clone();
-    let details_cl = details.clone();
-    let large_image_cl = large_image.clone();
-    let small_image_cl = small_image.clone();
-    let large_text_cl = large_text.clone();
-    let small_text_cl = small_text.clone();
-    let activity_type_cl = activity_type.clone();
-
-    // Весь `match`/блок ниже гарантирует, что никакой MutexGuard не "переживёт"
-    // время жизни `arc_client` или других временных переменных.
-    let res = match arc_client.try_lock() {
-        Ok(mut guard) => {
-            // Быстрый/синхронный путь — лок взят, выполняем set_activity прямо сейчас.
-            let mut activity = activity::Activity::new();
-            if !state_text_cl.is_empty() {
-                activity = activity.state(&state_text_cl);
-            }
-            if !details_cl.is_empty() {
-                activity = activity.details(&details_cl);
-            }
-
-            if let Some(t) = activity_type {
-                match t.to_lowercase().as_str() {
-                    "playing" => { activity = activity.activity_type(activity::ActivityType::Playing); }
-                    "listening" => { activity = activity.activity_type(activity::ActivityType::Listening); }
-                    "watching" => { activity = activity.activity_type(activity::ActivityType::Watching); }
-                    "competing" => { activity = activity.activity_type(activity::ActivityType::Competing); }
-                    other => { eprintln!("Unknown activity_type '{}', ignoring", other); }
-                }
-            }
-
-            if !large_image.is_empty() || !small_image.is_empty() || !large_text.is_empty() || !small_text.is_empty() {
-                let mut assets = activity::Assets::new();
-                if !large_image.is_empty() {
-                    assets = assets.large_image(&large_image);
-                }
-                if !small_image.is_empty() {
-                    assets = assets.small_image(&small_image);
-                }
-                if !large_text.is_empty() {
-                    assets = assets.large_text(&large_text);
-                }
-                if !small_text.is_empty() {
-                    assets = assets.small_text(&small_text);
-                }
-                activity = activity.assets(assets);
-            }
-
-            guard
-                .set_activity(activity)
-                .map_err(|e| format!("Failed to set activity: {:?}", e))
-        }
-
-        Err(std::sync::TryLockError::WouldBlock) => {
-            // Если лок занят — спауним фоновый поток и возвращаем Ok сразу.
-            let arc_for_thread = arc_client.clone();
-            std::thread::spawn(move || {
-                if let Ok(mut g) = arc_for_thread.lock() {
-                    let mut activity = activity::Activity::new();
-                    if !state_text_cl.is_empty() {
-                        activity = activity.state(&state_text_cl);
-                    }
-                    if !details_cl.is_empty() {
-                        activity = activity.details(&details_cl);
-                    }
-
-                    if let Some(t) = activity_type_cl {
-                        match t.to_lowercase().as_str() {
-                            "playing" => { activity = activity.activity_type(activity::ActivityType::Playing); }
-                            "listening" => { activity = activity.activity_type(activity::ActivityType::Listening); }
-                            "watching" => { activity = activity.activity_type(activity::ActivityType::Watching); }
-                            "competing" => { activity = activity.activity_type(activity::ActivityType::Competing); }
-                            other => { eprintln!("Unknown activity_type '{}', ignoring", other); }
-                        }
-                    }
-
-                    if !large_image_cl.is_empty() || !small_image_cl.is_empty() || !large_text_cl.is_empty() || !small_text_cl.is_empty() {
-                        let mut assets = activity::Assets::new();
-                        if !large_image_cl.is_empty() {
-                            assets = assets.large_image(&large_image_cl);
-                        }
-                        if !small_image_cl.is_empty() {
-                            assets = assets.small_image(&small_image_cl);
-                        }
-                        if !large_text_cl.is_empty() {
-                            assets = assets.large_text(&large_text_cl);
-                        }
-                        if !small_text_cl.is_empty() {
-                            assets = assets.small_text(&small_text_cl);
-                        }
-                        activity = activity.assets(assets);
-                    }
-
-                    if let Err(e) = g.set_activity(activity) {
-                        eprintln!("Background set_activity failed: {:?}", e);
-                    }
-                } else {
-                    eprintln!("Background update_rpc: failed to lock arc_client");
-                }
-            });
-
-            Ok(())
-        }
-
-        Err(std::sync::TryLockError::Poisoned(e)) => Err(format!("Mutex poisoned: {:?}", e)),
-    };
-
-    res
+    presence: Presence,
+    app_id: Option<String>,
+) -> Result<(), RpcError> {
+    let activity = PresenceBuilder::new(presence).build()?;
+    state
+        .pool
+        .worker(app_id.as_deref())?
+        .set_activity(activity)
+        .await
 }
 
-
-
 /// Очистить активность
 #[tauri::command]
-pub fn clear_rpc(state: State<'_, RpcState>) -> Result<(), String> {
-    let arc_client = {
-        let guard = state.discord.lock().map_err(|e| format!("Mutex poisoned: {:?}", e))?;
-        guard
-            .as_ref()
-            .ok_or_else(|| "RPC client not initialized. Call init_rpc first.".to_string())?
-            .clone()
-    };
-
-    let mut guard = arc_client.lock().map_err(|e| format!("Mutex poisoned: {:?}", e))?;
-    guard
-        .clear_activity()
-        .map_err(|e| format!("Failed to clear activity: {:?}", e))
+pub async fn clear_rpc(state: State<'_, RpcState>, app_id: Option<String>) -> Result<(), RpcError> {
+    state.pool.worker(app_id.as_deref())?.clear().await
 }
 
-/// Закрыть RPC клиент (close)
+/// Закрыть RPC клиент (close); без `app_id` — все соединения
 #[tauri::command]
-pub fn close_rpc(state: State<'_, RpcState>) -> Result<(), String> {
-    // Возьмём Option и если есть — закроем
-    let maybe_arc = {
-        let mut guard = state.discord.lock().map_err(|e| format!("Mutex poisoned: {:?}", e))?;
-        guard.take()
-    };
-
-    if let Some(arc_client) = maybe_arc {
-        // Закрываем (получаем mutable и вызываем close)
-        let mut client = arc_client.lock().map_err(|e| format!("Mutex poisoned: {:?}", e))?;
-        client
-            .close()
-            .map_err(|e| format!("Failed to close Discord IPC client: {:?}", e))?;
-    }
+pub async fn close_rpc(state: State<'_, RpcState>, app_id: Option<String>) -> Result<(), RpcError> {
+    state.pool.close(app_id.as_deref()).await
+}
 
-    println!("Discord RPC client closed.");
-    Ok(())
+/// Сколько ждать handshake от одного endpoint'а при диагностике
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Результат проверки одного discord-ipc-N
+#[derive(Debug, Clone, Serialize)]
+pub struct IpcProbeReport {
+    pub endpoint: String,
+    pub slot: Option<u32>,
+    pub ok: bool,
+    /// Время от открытия соединения до READY (или до ошибки)
+    pub latency_ms: u64,
+    pub user: Option<DiscordUser>,
+    pub build: Option<DiscordBuild>,
+    pub error: Option<RpcError>,
+    /// К этому endpoint'у подключится init_rpc — первый успешный в порядке перебора,
+    /// подходящий под InstanceSelector из init_rpc
+    pub selected: bool,
 }
 
+/// Найти все discord-ipc-N на текущей платформе и выполнить handshake с каждым.
+/// `app_id` по умолчанию — тот, с которым вызывался init_rpc.
 #[tauri::command]
-pub fn debug_ipc_pipes() -> Result<Vec<String>, String> {
-    // Только для Windows — на других платформах нет discord ipc pipes
-    if !cfg!(windows) {
-        return Err("debug_ipc_pipes: supported on Windows only".into());
-    }
-
-    // Попытка 1: читать \\.\pipe\ напрямую
-    match fs::read_dir(r"\\.\pipe\") {
-        Ok(entries) => {
-            let mut found = Vec::new();
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
-                    if name.contains("discord-ipc") {
-                        found.push(name.to_string());
-                    }
-                }
-            }
-            if found.is_empty() {
-                found.push("No Discord IPC pipes found".to_string());
-            }
-            return Ok(found);
-        }
-        Err(e) => {
-            // Попытка 2: powershell fallback (если read_dir недоступен по каким-то причинам)
-            let ps_cmd = r#"Get-ChildItem \\.\pipe\ | Where-Object Name -Match 'discord-ipc' | Select-Object -ExpandProperty Name"#;
-            let out = Command::new("powershell")
-                .args(&["-NoProfile", "-Command", ps_cmd])
-                .output();
-
-            match out {
-                Ok(output) => {
-                    if output.status.success() {
-                        let s = String::from_utf8_lossy(&output.stdout).to_string();
-                        let mut lines: Vec<String> = s
-                            .lines()
-                            .map(|l| l.trim().to_string())
-                            .filter(|l| !l.is_empty())
-                            .collect();
-                        if lines.is_empty() {
-                            lines.push("No Discord IPC pipes found".to_string());
-                        }
-                        return Ok(lines);
-                    } else {
-                        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                        return Err(format!(
-                            "Failed to enumerate pipes (read_dir error: {:?}). Powershell returned error: {}",
-                            e, stderr
-                        ));
-                    }
-                }
-                Err(e2) => {
-                    return Err(format!(
-                        "Failed to enumerate pipes: read_dir error: {:?}, powershell spawn error: {:?}",
-                        e, e2
-                    ));
-                }
-            }
-        }
-    }
+pub async fn debug_ipc_pipes(
+    state: State<'_, RpcState>,
+    app_id: Option<String>,
+) -> Result<Vec<IpcProbeReport>, RpcError> {
+    let app_id = app_id
+        .filter(|id| !id.is_empty())
+        .or_else(|| state.pool.status(None).app_id)
+        .ok_or_else(|| {
+            RpcError::new(
+                ErrorCode::NotInitialized,
+                "No app id to handshake with: pass app_id or call init_rpc first",
+            )
+            .with_hints(&[Hint::CallInitRpc])
+        })?;
+
+    let instance = state.pool.status(None).instance;
+    let mut selected = false;
+    let reports = discord_ipc::probe_endpoints(&app_id, PROBE_TIMEOUT)
+        .await
+        .into_iter()
+        .map(|probe| {
+            let ok = probe.result.is_ok();
+            let slot = discord_ipc::endpoint_slot(&probe.endpoint);
+            let (user, build, error) = match probe.result {
+                Ok(ready) => (Some(ready.user), Some(ready.config.build()), None),
+                Err(e) => (None, None, Some(RpcError::from(e))),
+            };
+            let eligible = build.is_some_and(|build| instance.matches(slot, build));
+            let report = IpcProbeReport {
+                slot,
+                endpoint: probe.endpoint.display().to_string(),
+                ok,
+                latency_ms: probe.latency.as_millis() as u64,
+                user,
+                build,
+                error,
+                selected: eligible && !selected,
+            };
+            selected |= eligible;
+            report
+        })
+        .collect();
+    Ok(reports)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -314,12 +177,272 @@ pub struct AllowedProcess {
     pub app_id: Option<String>,
     #[serde(default)]
     pub title_extract_patterns: Option<Vec<String>>,
+    /// Шаблоны текста активности; None — встроенный шаблон
+    #[serde(default)]
+    pub presence: Option<PresenceTemplate>,
 }
 
 
 #[tauri::command]
-pub fn get_allowed_processes() -> Result<Vec<AllowedProcess>, String> {
-    let json = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/allowed_processes.json"));
-    serde_json::from_str::<Vec<AllowedProcess>>(json)
-        .map_err(|e| format!("Failed to parse allowed_processes.json: {}", e))
+pub fn get_allowed_processes() -> Result<Vec<AllowedProcess>, RpcError> {
+    Ok(app_registry::current().processes())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::discord_ipc::IpcClient;
+    use crate::mock_discord::MockServer;
+    #[cfg(unix)]
+    use crate::rpc_worker::{ConnectionState, RpcWorker};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    pub(crate) const APP_ID: &str = "1234567890";
+
+    /// Пустой каталог одного теста (сокеты заглушки, apps.json)
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jrpce-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Отдельный endpoint для каждого теста, чтобы они не мешали друг другу и настоящему Discord
+    fn test_endpoint(name: &str) -> PathBuf {
+        #[cfg(unix)]
+        return temp_dir(name).join("discord-ipc-0");
+        #[cfg(windows)]
+        return PathBuf::from(format!(r"\\.\pipe\jrpce-test-{}-{}", std::process::id(), name));
+    }
+
+    async fn connected_client(server: &MockServer) -> IpcClient {
+        let mut client = IpcClient::new(APP_ID);
+        client.connect_endpoint(server.endpoint()).await.unwrap();
+        client
+    }
+
+    /// Дождаться, пока условие станет истинным (воркер и заглушка работают в фоне)
+    #[cfg(unix)]
+    pub(crate) async fn wait_for(what: &str, mut condition: impl FnMut() -> bool) {
+        for _ in 0..250 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("timed out waiting for {}", what);
+    }
+
+    #[tokio::test]
+    async fn set_activity_sends_built_presence() {
+        let server = MockServer::start(&test_endpoint("activity")).await.unwrap();
+        let mut client = connected_client(&server).await;
+        assert_eq!(client.ready().unwrap().user.username, "mock");
+
+        let activity = PresenceBuilder::new(Presence {
+            details: Some("Editing scene.c4d".to_string()),
+            state: Some("  ".to_string()),
+            large_image: Some("appicon".to_string()),
+            start_timestamp: Some(1_700_000_000),
+            ..Presence::default()
+        })
+        .build()
+        .unwrap();
+        client.set_activity(activity).await.unwrap();
+
+        assert_eq!(
+            server.activities(),
+            vec![json!({
+                "details": "Editing scene.c4d",
+                "timestamps": { "start": 1_700_000_000 },
+                "assets": { "large_image": "appicon" }
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_handshake_is_invalid_app_id() {
+        let server = MockServer::start(&test_endpoint("handshake")).await.unwrap();
+        server.reject_handshakes(1);
+
+        let mut client = IpcClient::new(APP_ID);
+        let err = RpcError::from(client.connect_endpoint(server.endpoint()).await.unwrap_err());
+        assert_eq!(err.code, ErrorCode::InvalidAppId);
+        assert_eq!(err.details, Some(json!({ "close_code": 4000 })));
+    }
+
+    #[tokio::test]
+    async fn command_error_is_discord_rejected() {
+        let server = MockServer::start(&test_endpoint("command-error")).await.unwrap();
+        let mut client = connected_client(&server).await;
+        server.fail_next_command(4000, "child \"activity\" fails");
+
+        let err = RpcError::from(client.set_activity(json!({ "state": "Idle" })).await.unwrap_err());
+        assert_eq!(err.code, ErrorCode::DiscordRejected);
+        assert_eq!(err.details, Some(json!({ "discord_code": 4000 })));
+
+        // Соединение остаётся рабочим
+        client.set_activity(json!({ "state": "Idle" })).await.unwrap();
+        assert_eq!(server.activities().len(), 2);
+    }
+
+    /// Воркер ищет Discord через XDG_RUNTIME_DIR — переменная общая, поэтому такие тесты
+    /// (и тесты rpc_pool) идут по одному
+    #[cfg(unix)]
+    static WORKER_ENV: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    #[cfg(unix)]
+    pub(crate) async fn worker_server(name: &str) -> (tokio::sync::MutexGuard<'static, ()>, MockServer) {
+        let guard = WORKER_ENV.lock().await;
+        // Клиент с InstanceSelector::Any перебирает XDG_RUNTIME_DIR первым
+        let dir = temp_dir(name);
+        std::env::set_var("XDG_RUNTIME_DIR", &dir);
+        let server = MockServer::start(&dir.join("discord-ipc-0")).await.unwrap();
+        (guard, server)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn worker_reconnects_and_restores_activity() {
+        let (_env, server) = worker_server("reconnect").await;
+
+        let worker = RpcWorker::spawn(Arc::new(|_| {}));
+        let backoff = BackoffConfig {
+            initial_ms: 200,
+            max_ms: 1_000,
+            multiplier: 2.0,
+        };
+        worker
+            .connect(APP_ID.to_string(), InstanceSelector::Any, backoff)
+            .await
+            .unwrap();
+        assert_eq!(worker.status().state, ConnectionState::Connected);
+
+        let activity = json!({ "details": "Editing scene.c4d" });
+        worker.set_activity(activity.clone()).await.unwrap();
+        assert_eq!(server.activities(), vec![activity.clone()]);
+
+        server.disconnect_all();
+        wait_for("backoff", || worker.status().state == ConnectionState::Backoff).await;
+        let status = worker.status();
+        assert_eq!(status.reconnect_attempt, 1);
+        assert_eq!(status.retry_delay_ms, Some(200));
+        assert_eq!(status.last_error.map(|e| e.code), Some(ErrorCode::ConnectionLost));
+
+        wait_for("re-sent activity", || server.activities().len() == 2).await;
+        assert_eq!(worker.status().state, ConnectionState::Connected);
+        assert_eq!(server.activities(), vec![activity.clone(), activity]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn worker_rate_limits_and_coalesces_activity() {
+        let (_env, server) = worker_server("rate-limit").await;
+
+        let worker = RpcWorker::spawn(Arc::new(|_| {}));
+        worker
+            .connect(APP_ID.to_string(), InstanceSelector::Any, BackoffConfig::default())
+            .await
+            .unwrap();
+        let scene = |i: usize| json!({ "details": format!("Editing scene_{}.c4d", i) });
+
+        // Повтор той же активности не тратит токен и не шлёт кадр
+        worker.set_activity(scene(0)).await.unwrap();
+        worker.set_activity(scene(0)).await.unwrap();
+        assert_eq!(server.activities(), vec![scene(0)]);
+
+        for i in 1..7 {
+            worker.set_activity(scene(i)).await.unwrap();
+        }
+        // Пачка из 5 уходит сразу, 6-я и 7-я ждут токена
+        assert_eq!(server.activities(), (0..5).map(scene).collect::<Vec<_>>());
+
+        // Через ~4 с уходит только последняя: 6-ю перезаписала 7-я
+        wait_for("deferred activity", || server.activities().len() == 6).await;
+        let expected: Vec<_> = (0..5).chain([6]).map(scene).collect();
+        assert_eq!(server.activities(), expected);
+
+        worker.set_activity(scene(6)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(server.activities(), expected);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn worker_does_not_retry_invalid_app_id() {
+        let (_env, server) = worker_server("invalid-app-id").await;
+        server.reject_handshakes(1);
+
+        let worker = RpcWorker::spawn(Arc::new(|_| {}));
+        let err = worker
+            .connect(APP_ID.to_string(), InstanceSelector::Any, BackoffConfig::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidAppId);
+
+        let status = worker.status();
+        assert_eq!(status.state, ConnectionState::Disconnected);
+        assert_eq!(status.retry_delay_ms, None);
+        assert_eq!(status.last_error.map(|e| e.code), Some(ErrorCode::InvalidAppId));
+        // Одна попытка handshake, без повторов
+        assert_eq!(server.received().len(), 1);
+        assert_eq!(
+            worker.set_activity(json!({ "state": "Idle" })).await.unwrap_err().code,
+            ErrorCode::NotInitialized
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn worker_stops_reconnecting_when_app_id_is_rejected() {
+        let (_env, server) = worker_server("reconnect-rejected").await;
+
+        let worker = RpcWorker::spawn(Arc::new(|_| {}));
+        let backoff = BackoffConfig {
+            initial_ms: 100,
+            max_ms: 100,
+            multiplier: 1.0,
+        };
+        worker
+            .connect(APP_ID.to_string(), InstanceSelector::Any, backoff)
+            .await
+            .unwrap();
+
+        // Приложение удалили, пока соединение было открыто
+        server.reject_handshakes(u32::MAX);
+        server.disconnect_all();
+        wait_for("rejected reconnect", || {
+            worker.status().last_error.is_some_and(|e| e.code == ErrorCode::InvalidAppId)
+        })
+        .await;
+
+        let status = worker.status();
+        assert_eq!(status.state, ConnectionState::Disconnected);
+        assert_eq!(status.reconnect_attempt, 0);
+        assert_eq!(status.retry_delay_ms, None);
+        // Начальный handshake и один отвергнутый при переподключении
+        let handshakes = server.received().len();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(server.received().len(), handshakes);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(start_paused = true)]
+    async fn silent_endpoint_times_out_handshake() {
+        let path = test_endpoint("silent");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        // Принимает соединение, но READY так и не отправляет
+        let _accept = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+            drop(stream);
+        });
+
+        let mut client = IpcClient::new(APP_ID);
+        let err = client.connect_endpoint(&path).await.unwrap_err();
+        assert!(err.is_connection_lost());
+        assert_eq!(RpcError::from(err).code, ErrorCode::ConnectionLost);
+        assert!(client.ready().is_none());
+    }
 }
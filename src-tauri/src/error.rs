@@ -0,0 +1,163 @@
+// src/error.rs
+// Единый тип ошибки для команд Tauri: стабильный код, по которому фронтенд выбирает
+// текст на языке пользователя, сообщение для логов и необязательные подсказки
+use crate::discord_ipc::IpcError;
+use crate::presence::PresenceError;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Close-код Discord для неизвестного client_id
+const CLOSE_INVALID_CLIENT_ID: i64 = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Ни одного discord-ipc-N: Discord не запущен или недоступен из песочницы
+    DiscordNotRunning,
+    /// Discord запущен, но не тот экземпляр, что выбран в настройках (индекс или сборка)
+    InstanceNotFound,
+    /// Discord отклонил handshake с этим app_id
+    InvalidAppId,
+    /// Команда пришла до init_rpc или после close_rpc
+    NotInitialized,
+    ConnectionLost,
+    /// Discord ответил evt: ERROR на команду
+    DiscordRejected,
+    ProtocolError,
+    /// Активность не прошла проверку, подробности в `details`
+    InvalidPresence,
+    WorkerUnavailable,
+    /// Нет foreground-окна (Win32) или выбранного окна уже нет в списке
+    WindowNotFound,
+    WindowOperationFailed,
+    /// Описание приложения не прошло проверку, ошибки по полям в `details.fields`
+    InvalidApp,
+    /// update_app / remove_app для процесса, которого нет в реестре
+    AppNotFound,
+    Internal,
+}
+
+/// Подсказки тоже передаются кодами — текст подбирает фронтенд
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hint {
+    /// Запустить Discord Desktop
+    StartDiscord,
+    /// Версия из Microsoft Store и браузерный Discord не поддерживают IPC
+    UseDesktopClient,
+    /// Проверить App ID в Discord Developer Portal
+    CheckAppId,
+    /// Дождаться, пока Discord загрузится и закончит обновление
+    WaitForDiscord,
+    /// Посмотреть вывод debug_ipc_pipes и логи Discord
+    RunDebugIpcPipes,
+    /// Вызвать init_rpc
+    CallInitRpc,
+    /// Проверить выбранный экземпляр Discord (discord-ipc-N / stable / ptb / canary) или выбрать «любой»
+    CheckInstanceSetting,
+    RestartApp,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<Hint>,
+    /// Структурированные подробности (например, поле активности или код ошибки Discord)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            hints: Vec::new(),
+            details: None,
+        }
+    }
+
+    pub fn with_hints(mut self, hints: &[Hint]) -> Self {
+        self.hints.extend_from_slice(hints);
+        self
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Дописать, что именно делали, перед исходным сообщением
+    pub fn context(mut self, action: impl fmt::Display) -> Self {
+        self.message = format!("{}: {}", action, self.message);
+        self
+    }
+
+    pub fn not_initialized() -> Self {
+        Self::new(
+            ErrorCode::NotInitialized,
+            "RPC client not initialized. Call init_rpc first.",
+        )
+        .with_hints(&[Hint::CallInitRpc])
+    }
+
+    pub fn worker_unavailable(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::WorkerUnavailable, message).with_hints(&[Hint::RestartApp])
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<IpcError> for RpcError {
+    fn from(e: IpcError) -> Self {
+        let message = e.to_string();
+        match e {
+            IpcError::NotFound => RpcError::new(ErrorCode::DiscordNotRunning, message).with_hints(&[
+                Hint::StartDiscord,
+                Hint::UseDesktopClient,
+                Hint::RunDebugIpcPipes,
+            ]),
+            IpcError::Closed { code, .. } if code == CLOSE_INVALID_CLIENT_ID => {
+                RpcError::new(ErrorCode::InvalidAppId, message)
+                    .with_hints(&[Hint::CheckAppId])
+                    .with_details(serde_json::json!({ "close_code": code }))
+            }
+            IpcError::Closed { code, .. } => RpcError::new(ErrorCode::ConnectionLost, message)
+                .with_hints(&[Hint::WaitForDiscord])
+                .with_details(serde_json::json!({ "close_code": code })),
+            IpcError::NotConnected | IpcError::Io(_) => {
+                RpcError::new(ErrorCode::ConnectionLost, message).with_hints(&[Hint::WaitForDiscord])
+            }
+            IpcError::Discord { code, .. } => RpcError::new(ErrorCode::DiscordRejected, message)
+                .with_details(serde_json::json!({ "discord_code": code })),
+            IpcError::Protocol(_) => RpcError::new(ErrorCode::ProtocolError, message),
+            IpcError::NoMatchingInstance(_) => RpcError::new(ErrorCode::InstanceNotFound, message)
+                .with_hints(&[Hint::CheckInstanceSetting, Hint::RunDebugIpcPipes]),
+        }
+    }
+}
+
+impl From<PresenceError> for RpcError {
+    fn from(e: PresenceError) -> Self {
+        let details = serde_json::to_value(&e).ok();
+        RpcError {
+            code: ErrorCode::InvalidPresence,
+            message: e.to_string(),
+            hints: Vec::new(),
+            details,
+        }
+    }
+}
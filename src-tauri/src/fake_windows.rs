@@ -0,0 +1,112 @@
+// src/fake_windows.rs
+// Окна и процессы из JSON-сценария вместо настоящего рабочего стола (фича `fake-windows`, тесты).
+// Сценарий включается путём в $JRPCE_WINDOWS_FIXTURE (пример — fixtures/windows.json);
+// во время работы его можно заменить командой fake_windows_load, имитируя открытие,
+// закрытие и переименование окон.
+#[cfg(feature = "fake-windows")]
+use crate::error::RpcError;
+use crate::window_provider::{ProcessInfo, RawWindow, WindowProvider};
+#[cfg(feature = "fake-windows")]
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(feature = "fake-windows")]
+const FIXTURE_ENV: &str = "JRPCE_WINDOWS_FIXTURE";
+
+/// Сценарий: окна в порядке перечисления, процессы по PID и окно в фокусе
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fixture {
+    pub windows: Vec<RawWindow>,
+    pub processes: HashMap<u32, ProcessInfo>,
+    pub focused: Option<isize>,
+}
+
+impl Fixture {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+pub struct FakeWindowProvider {
+    fixture: Mutex<Fixture>,
+}
+
+impl FakeWindowProvider {
+    pub fn new(fixture: Fixture) -> Self {
+        Self {
+            fixture: Mutex::new(fixture),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Fixture> {
+        self.fixture.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Заменить сценарий целиком
+    #[cfg_attr(all(windows, not(feature = "fake-windows")), allow(dead_code))]
+    pub fn load(&self, fixture: Fixture) {
+        *self.lock() = fixture;
+    }
+}
+
+impl WindowProvider for FakeWindowProvider {
+    fn list_windows(&self) -> Vec<RawWindow> {
+        self.lock()
+            .windows
+            .iter()
+            .filter(|w| !w.title.is_empty())
+            .cloned()
+            .collect()
+    }
+
+    fn window(&self, hwnd: isize) -> Option<RawWindow> {
+        self.lock()
+            .windows
+            .iter()
+            .find(|w| w.hwnd == hwnd && !w.title.is_empty())
+            .cloned()
+    }
+
+    fn process_info(&self, pid: u32) -> Option<ProcessInfo> {
+        self.lock().processes.get(&pid).cloned()
+    }
+
+    fn focused_window(&self) -> Option<isize> {
+        self.lock().focused
+    }
+
+    fn is_process_alive(&self, pid: u32) -> bool {
+        self.lock().processes.contains_key(&pid)
+    }
+}
+
+/// None — переменная не задана, работает настоящий бэкенд
+#[cfg(feature = "fake-windows")]
+pub static PROVIDER: Lazy<Option<FakeWindowProvider>> = Lazy::new(|| {
+    let path = std::env::var(FIXTURE_ENV).ok()?;
+    let fixture = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| Fixture::from_json(&json).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to load window fixture {}: {}", path, e);
+            Fixture::default()
+        });
+    println!("Using fake windows from {}", path);
+    Some(FakeWindowProvider::new(fixture))
+});
+
+#[cfg(feature = "fake-windows")]
+#[tauri::command]
+pub fn fake_windows_load(fixture: Fixture) -> Result<(), RpcError> {
+    let provider = PROVIDER.as_ref().ok_or_else(|| {
+        RpcError::internal(format!(
+            "Set {} to use the fake window provider",
+            FIXTURE_ENV
+        ))
+    })?;
+    provider.load(fixture);
+    Ok(())
+}
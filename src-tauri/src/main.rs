@@ -1,14 +1,59 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod app_registry;
 mod windows_api;
+#[cfg(not(windows))]
+mod x11_api;
+mod discord_ipc;
 mod discord_rpc;
+mod error;
+mod presence;
+mod presence_engine;
+mod presence_template;
+mod rate_limit;
+mod rpc_events;
+mod rpc_pool;
+mod rpc_worker;
+mod window_focus;
+mod window_provider;
+mod window_watcher;
+#[cfg(any(test, feature = "mock-discord"))]
+mod mock_discord;
+#[cfg(any(test, feature = "fake-windows"))]
+mod fake_windows;
 
 use discord_rpc::RpcState;
+use presence_engine::PresenceEngine;
+use std::sync::Arc;
+use tauri::Manager;
 
 fn main() {
-    tauri::Builder::default()
-        // состояние для Discord RPC
-        .manage(RpcState::new())
+    let builder = tauri::Builder::default()
+        // состояние для Discord RPC; воркеру нужен AppHandle для событий, поэтому в setup.
+        // Движок активности стартует сразу и работает, даже когда webview скрыт.
+        .setup(|app| {
+            // apps.json пользователя лежит рядом с config.json
+            match app.path().app_config_dir() {
+                Ok(dir) => app_registry::init(&dir),
+                Err(e) => eprintln!("Config dir unavailable, using bundled apps only: {}", e),
+            }
+            let rpc = RpcState::new(app.handle().clone());
+            let sink = presence_engine::tauri_sink(app.handle().clone());
+            let windows = Arc::new(window_provider::system());
+            app.manage(PresenceEngine::spawn(rpc.pool.clone(), windows, sink));
+            app.manage(rpc);
+            Ok(())
+        })
+        // Иконки приложений: свои из папки конфигурации и встроенные из ресурсов
+        .register_uri_scheme_protocol(app_registry::ICON_SCHEME, |ctx, request| {
+            app_registry::icon_response(ctx.app_handle(), &request)
+        });
+
+    // Оффлайн-разработка без Discord: `npm run tauri dev -- --features mock-discord`
+    #[cfg(feature = "mock-discord")]
+    let builder = builder.manage(mock_discord::start_dev_server());
+
+    builder
         .invoke_handler(tauri::generate_handler![
             // Windows API
             windows_api::get_windows_list,
@@ -25,6 +70,40 @@ fn main() {
             discord_rpc::update_rpc,
             discord_rpc::clear_rpc,
             discord_rpc::close_rpc,
+            discord_rpc::get_rpc_status,
+            discord_rpc::get_rpc_pool_status,
+            discord_rpc::get_discord_user,
+
+            // Реестр приложений
+            app_registry::list_apps,
+            app_registry::add_app,
+            app_registry::update_app,
+            app_registry::remove_app,
+            app_registry::test_title_patterns,
+
+            // Движок активности
+            presence_engine::get_presence_state,
+            presence_engine::select_window,
+            presence_engine::pause_presence,
+            presence_engine::resume_presence,
+            presence_engine::configure_presence,
+            presence_engine::refresh_windows,
+
+            // Заглушка Discord IPC
+            #[cfg(feature = "mock-discord")]
+            mock_discord::mock_discord_received,
+            #[cfg(feature = "mock-discord")]
+            mock_discord::mock_discord_activities,
+            #[cfg(feature = "mock-discord")]
+            mock_discord::mock_discord_reject_handshakes,
+            #[cfg(feature = "mock-discord")]
+            mock_discord::mock_discord_fail_next,
+            #[cfg(feature = "mock-discord")]
+            mock_discord::mock_discord_disconnect,
+
+            // Окна из JSON-сценария
+            #[cfg(feature = "fake-windows")]
+            fake_windows::fake_windows_load,
         ])
         // плагин для работы с файлами
         .plugin(tauri_plugin_fs::init())
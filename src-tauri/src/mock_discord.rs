@@ -0,0 +1,307 @@
+// src/mock_discord.rs
+// Локальная заглушка Discord IPC для оффлайн-разработки (фича `mock-discord`) и тестов.
+// Говорит на том же протоколе (handshake / FRAME / CLOSE), отвечает READY и на SET_ACTIVITY,
+// записывает всё полученное и умеет имитировать ошибки и обрывы соединения.
+use crate::discord_ipc::{self, read_frame, write_frame, Opcode};
+use serde_json::{json, Value};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "mock-discord")]
+use tauri::State;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Пользователь, которого заглушка отдаёт в READY
+fn mock_ready() -> Value {
+    json!({
+        "cmd": "DISPATCH",
+        "evt": "READY",
+        "nonce": null,
+        "data": {
+            "v": 1,
+            "config": {
+                "cdn_host": "cdn.discordapp.com",
+                "api_endpoint": "//discord.com/api",
+                "environment": "production"
+            },
+            "user": {
+                "id": "100000000000000001",
+                "username": "mock",
+                "discriminator": "0",
+                "global_name": "Mock User",
+                "avatar": null
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Каждый полученный payload (включая handshake) в порядке поступления
+    received: Vec<Value>,
+    /// Столько следующих handshake'ов будет отклонено CLOSE-кадром
+    reject_handshakes: u32,
+    /// Ошибка, которую получит следующая команда
+    next_error: Option<(i64, String)>,
+}
+
+pub struct MockServer {
+    endpoint: PathBuf,
+    shared: Arc<Mutex<Shared>>,
+    /// Поколение соединений: каждое увеличение обрывает все открытые к этому моменту
+    disconnect: Arc<watch::Sender<u64>>,
+    accept_task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Поднять сервер на конкретном пути (unix-сокет или \\.\pipe\...)
+    pub async fn start(endpoint: &Path) -> io::Result<Self> {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let disconnect = Arc::new(watch::Sender::new(0));
+        let accept_task = spawn_accept_loop(endpoint, shared.clone(), disconnect.clone())?;
+
+        Ok(Self {
+            endpoint: endpoint.to_path_buf(),
+            shared,
+            disconnect,
+            accept_task,
+        })
+    }
+
+    /// Занять первый свободный discord-ipc-N из тех, что перебирает клиент.
+    /// Если раньше в порядке перебора отвечает настоящий Discord, клиент с InstanceSelector::Any
+    /// подключится к нему, а не к заглушке, — поэтому в таком случае сервер не поднимается.
+    #[cfg_attr(not(feature = "mock-discord"), allow(dead_code))]
+    pub async fn start_on_free_slot() -> io::Result<Self> {
+        for path in discord_ipc::candidate_endpoints() {
+            match Self::start(&path).await {
+                Ok(server) => return Ok(server),
+                Err(e) if discord_ipc::endpoint_alive(&path).await => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!(
+                            "{} is served by a running Discord ({}); quit Discord to use mock-discord",
+                            path.display(),
+                            e
+                        ),
+                    ));
+                }
+                // Брошенный сокет: клиент его тоже пропустит
+                Err(_) => {}
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::AddrInUse, "no free discord-ipc slot"))
+    }
+
+    #[cfg_attr(not(feature = "mock-discord"), allow(dead_code))]
+    pub fn endpoint(&self) -> &Path {
+        &self.endpoint
+    }
+
+    /// Все полученные payload'ы
+    pub fn received(&self) -> Vec<Value> {
+        self.shared.lock().map(|s| s.received.clone()).unwrap_or_default()
+    }
+
+    /// Только `args.activity` из команд SET_ACTIVITY (null — очистка)
+    pub fn activities(&self) -> Vec<Value> {
+        self.received()
+            .into_iter()
+            .filter(|p| p["cmd"] == "SET_ACTIVITY")
+            .map(|p| p["args"]["activity"].clone())
+            .collect()
+    }
+
+    /// Отклонить следующие `count` handshake'ов, как Discord делает с неверным client_id
+    pub fn reject_handshakes(&self, count: u32) {
+        if let Ok(mut s) = self.shared.lock() {
+            s.reject_handshakes = count;
+        }
+    }
+
+    /// Ответить на следующую команду evt: ERROR
+    pub fn fail_next_command(&self, code: i64, message: &str) {
+        if let Ok(mut s) = self.shared.lock() {
+            s.next_error = Some((code, message.to_string()));
+        }
+    }
+
+    /// Оборвать все текущие соединения (как при перезапуске Discord)
+    #[cfg_attr(all(windows, not(feature = "mock-discord")), allow(dead_code))]
+    pub fn disconnect_all(&self) {
+        self.disconnect.send_modify(|generation| *generation += 1);
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        self.disconnect.send_modify(|generation| *generation += 1);
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.endpoint);
+    }
+}
+
+#[cfg(unix)]
+fn spawn_accept_loop(
+    endpoint: &Path,
+    shared: Arc<Mutex<Shared>>,
+    disconnect: Arc<watch::Sender<u64>>,
+) -> io::Result<JoinHandle<()>> {
+    let listener = tokio::net::UnixListener::bind(endpoint)?;
+    Ok(tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_connection(stream, shared.clone(), disconnect.subscribe()));
+        }
+    }))
+}
+
+#[cfg(windows)]
+fn spawn_accept_loop(
+    endpoint: &Path,
+    shared: Arc<Mutex<Shared>>,
+    disconnect: Arc<watch::Sender<u64>>,
+) -> io::Result<JoinHandle<()>> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let endpoint = endpoint.to_path_buf();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&endpoint)?;
+    Ok(tokio::spawn(async move {
+        loop {
+            if server.connect().await.is_err() {
+                break;
+            }
+            let next = match ServerOptions::new().create(&endpoint) {
+                Ok(next) => next,
+                Err(_) => break,
+            };
+            let connected = std::mem::replace(&mut server, next);
+            tokio::spawn(serve_connection(connected, shared.clone(), disconnect.subscribe()));
+        }
+    }))
+}
+
+fn record(shared: &Mutex<Shared>, payload: &Value) {
+    if let Ok(mut s) = shared.lock() {
+        s.received.push(payload.clone());
+    }
+}
+
+async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    shared: Arc<Mutex<Shared>>,
+    // Подписка с момента accept: обрыв не теряется, даже если задача в это время пишет кадр
+    mut disconnect: watch::Receiver<u64>,
+) {
+    // Handshake
+    let Ok((op, handshake)) = read_frame(&mut stream).await else {
+        return;
+    };
+    record(&shared, &handshake);
+    if Opcode::from_u32(op) != Some(Opcode::Handshake) {
+        return;
+    }
+
+    let reject = shared
+        .lock()
+        .map(|mut s| {
+            let reject = s.reject_handshakes > 0;
+            s.reject_handshakes = s.reject_handshakes.saturating_sub(1);
+            reject
+        })
+        .unwrap_or(false);
+    if reject {
+        let close = json!({ "code": 4000, "message": "Invalid Client ID" });
+        let _ = write_frame(&mut stream, Opcode::Close, &close).await;
+        return;
+    }
+    if write_frame(&mut stream, Opcode::Frame, &mock_ready()).await.is_err() {
+        return;
+    }
+
+    loop {
+        let frame = tokio::select! {
+            frame = read_frame(&mut stream) => frame,
+            _ = disconnect.changed() => return,
+        };
+        let Ok((op, payload)) = frame else {
+            return;
+        };
+        record(&shared, &payload);
+
+        let (reply_op, reply) = match Opcode::from_u32(op) {
+            Some(Opcode::Frame) => {
+                let injected = shared.lock().ok().and_then(|mut s| s.next_error.take());
+                let reply = match injected {
+                    Some((code, message)) => json!({
+                        "cmd": payload["cmd"],
+                        "evt": "ERROR",
+                        "nonce": payload["nonce"],
+                        "data": { "code": code, "message": message }
+                    }),
+                    None => json!({
+                        "cmd": payload["cmd"],
+                        "evt": null,
+                        "nonce": payload["nonce"],
+                        "data": payload["args"]["activity"]
+                    }),
+                };
+                (Opcode::Frame, reply)
+            }
+            Some(Opcode::Ping) => (Opcode::Pong, payload),
+            Some(Opcode::Close) | None => return,
+            _ => continue,
+        };
+        if write_frame(&mut stream, reply_op, &reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Заглушка, поднятая при старте приложения с фичей `mock-discord`
+#[cfg(feature = "mock-discord")]
+pub struct MockState(pub MockServer);
+
+#[cfg(feature = "mock-discord")]
+pub fn start_dev_server() -> MockState {
+    let server = tauri::async_runtime::block_on(MockServer::start_on_free_slot())
+        .expect("failed to start mock Discord IPC server");
+    println!("Mock Discord IPC listening on {}", server.endpoint().display());
+    MockState(server)
+}
+
+// Команды для ручной проверки из devtools фронтенда
+
+#[cfg(feature = "mock-discord")]
+#[tauri::command]
+pub fn mock_discord_received(state: State<'_, MockState>) -> Vec<Value> {
+    state.0.received()
+}
+
+#[cfg(feature = "mock-discord")]
+#[tauri::command]
+pub fn mock_discord_activities(state: State<'_, MockState>) -> Vec<Value> {
+    state.0.activities()
+}
+
+#[cfg(feature = "mock-discord")]
+#[tauri::command]
+pub fn mock_discord_reject_handshakes(state: State<'_, MockState>, count: u32) {
+    state.0.reject_handshakes(count);
+}
+
+#[cfg(feature = "mock-discord")]
+#[tauri::command]
+pub fn mock_discord_fail_next(state: State<'_, MockState>, code: i64, message: String) {
+    state.0.fail_next_command(code, &message);
+}
+
+#[cfg(feature = "mock-discord")]
+#[tauri::command]
+pub fn mock_discord_disconnect(state: State<'_, MockState>) {
+    state.0.disconnect_all();
+}
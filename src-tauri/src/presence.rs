@@ -0,0 +1,499 @@
+// src/presence.rs
+// Типизированное описание активности и единственное место, где из него собирается activity::Activity
+use crate::error::RpcError;
+use discord_rich_presence::activity;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Лимиты Discord на поля активности
+pub(crate) const TEXT_MIN_CHARS: usize = 2;
+const TEXT_MAX_CHARS: usize = 128;
+const BUTTON_LABEL_MAX_CHARS: usize = 32;
+const BUTTON_URL_MAX_CHARS: usize = 512;
+const MAX_BUTTONS: usize = 2;
+
+const ACTIVITY_TYPES: [&str; 4] = ["playing", "listening", "watching", "competing"];
+const STATUS_DISPLAY_TYPES: [&str; 3] = ["name", "state", "details"];
+
+/// Кнопка под активностью (например, ссылка на портфолио или ArtStation)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PresenceButton {
+    pub label: String,
+    pub url: String,
+}
+
+/// Активность в том виде, в каком её присылает фронтенд. Пустые строки означают «не задано».
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Presence {
+    pub name: Option<String>,
+    pub details: Option<String>,
+    pub state: Option<String>,
+    pub large_image: Option<String>,
+    pub large_text: Option<String>,
+    pub small_image: Option<String>,
+    pub small_text: Option<String>,
+    /// playing / listening / watching / competing
+    pub activity_type: Option<String>,
+    /// name / state / details
+    pub status_display_type: Option<String>,
+    /// Unix-время в секундах; обычно это время запуска процесса из WindowInfo
+    pub start_timestamp: Option<i64>,
+    pub end_timestamp: Option<i64>,
+    pub buttons: Vec<PresenceButton>,
+    pub party_id: Option<String>,
+    /// [текущий размер, максимальный]
+    pub party_size: Option<[i32; 2]>,
+}
+
+/// Ошибка проверки активности; `code` позволяет фронтенду выбрать текст подсказки
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum PresenceError {
+    TooShort { field: String, min: usize, actual: usize },
+    InvalidEnum { field: String, value: String, expected: Vec<String> },
+    TooManyButtons { max: usize, actual: usize },
+    InvalidUrl { field: String, reason: String },
+    InvalidParty { reason: String },
+}
+
+impl fmt::Display for PresenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresenceError::TooShort { field, min, actual } => {
+                write!(f, "{} must be at least {} characters long, got {}", field, min, actual)
+            }
+            PresenceError::InvalidEnum { field, value, expected } => {
+                write!(f, "Unknown {} '{}', expected one of: {}", field, value, expected.join(", "))
+            }
+            PresenceError::TooManyButtons { max, actual } => {
+                write!(f, "At most {} buttons are allowed, got {}", max, actual)
+            }
+            PresenceError::InvalidUrl { field, reason } => write!(f, "{}: {}", field, reason),
+            PresenceError::InvalidParty { reason } => write!(f, "party: {}", reason),
+        }
+    }
+}
+
+/// Обрезать строку до `max_chars` символов, не разрывая графемы; обрезанное помечается «…»
+pub fn truncate_graphemes(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        return value.to_string();
+    }
+
+    let budget = max_chars.saturating_sub(1);
+    let mut out = String::new();
+    let mut used = 0;
+    for grapheme in value.graphemes(true) {
+        let len = grapheme.chars().count();
+        if used + len > budget {
+            break;
+        }
+        out.push_str(grapheme);
+        used += len;
+    }
+    out.push('…');
+    out
+}
+
+/// Пустые и пробельные строки считаем незаданными
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.trim().is_empty())
+}
+
+fn normalize_text(field: &str, value: Option<String>) -> Result<Option<String>, PresenceError> {
+    let Some(value) = non_empty(value) else {
+        return Ok(None);
+    };
+    let len = value.chars().count();
+    if len < TEXT_MIN_CHARS {
+        return Err(PresenceError::TooShort {
+            field: field.to_string(),
+            min: TEXT_MIN_CHARS,
+            actual: len,
+        });
+    }
+    Ok(Some(truncate_graphemes(&value, TEXT_MAX_CHARS)))
+}
+
+fn normalize_enum(
+    field: &str,
+    value: Option<String>,
+    allowed: &[&str],
+) -> Result<Option<String>, PresenceError> {
+    let Some(value) = non_empty(value) else {
+        return Ok(None);
+    };
+    let lower = value.to_lowercase();
+    if !allowed.contains(&lower.as_str()) {
+        return Err(PresenceError::InvalidEnum {
+            field: field.to_string(),
+            value,
+            expected: allowed.iter().map(|s| s.to_string()).collect(),
+        });
+    }
+    Ok(Some(lower))
+}
+
+fn normalize_buttons(buttons: Vec<PresenceButton>) -> Result<Vec<PresenceButton>, PresenceError> {
+    if buttons.len() > MAX_BUTTONS {
+        return Err(PresenceError::TooManyButtons {
+            max: MAX_BUTTONS,
+            actual: buttons.len(),
+        });
+    }
+
+    buttons
+        .into_iter()
+        .enumerate()
+        .map(|(i, button)| {
+            let field = format!("buttons[{}].url", i);
+            if button.url.chars().count() > BUTTON_URL_MAX_CHARS {
+                return Err(PresenceError::InvalidUrl {
+                    field,
+                    reason: format!("must be at most {} characters long", BUTTON_URL_MAX_CHARS),
+                });
+            }
+            if !(button.url.starts_with("https://") || button.url.starts_with("http://")) {
+                return Err(PresenceError::InvalidUrl {
+                    field,
+                    reason: "must be an http(s) URL".to_string(),
+                });
+            }
+            let label = button.label.trim();
+            if label.is_empty() {
+                return Err(PresenceError::TooShort {
+                    field: format!("buttons[{}].label", i),
+                    min: 1,
+                    actual: 0,
+                });
+            }
+            Ok(PresenceButton {
+                label: truncate_graphemes(label, BUTTON_LABEL_MAX_CHARS),
+                url: button.url,
+            })
+        })
+        .collect()
+}
+
+fn validate_party_size(size: Option<[i32; 2]>) -> Result<(), PresenceError> {
+    if let Some([current, max]) = size {
+        if current < 1 || max < current {
+            return Err(PresenceError::InvalidParty {
+                reason: format!(
+                    "size must be [current, max] with 1 <= current <= max, got [{}, {}]",
+                    current, max
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Проверяет и нормализует Presence и превращает его в JSON активности для SET_ACTIVITY
+pub struct PresenceBuilder {
+    presence: Presence,
+}
+
+impl PresenceBuilder {
+    pub fn new(presence: Presence) -> Self {
+        Self { presence }
+    }
+
+    /// Провалидировать поля: длинные строки обрезаются, неверные значения дают PresenceError
+    fn normalize(self) -> Result<Presence, PresenceError> {
+        let p = self.presence;
+        validate_party_size(p.party_size)?;
+
+        Ok(Presence {
+            name: normalize_text("name", p.name)?,
+            details: normalize_text("details", p.details)?,
+            state: normalize_text("state", p.state)?,
+            large_image: non_empty(p.large_image),
+            large_text: normalize_text("large_text", p.large_text)?,
+            small_image: non_empty(p.small_image),
+            small_text: normalize_text("small_text", p.small_text)?,
+            activity_type: normalize_enum("activity_type", p.activity_type, &ACTIVITY_TYPES)?,
+            status_display_type: normalize_enum(
+                "status_display_type",
+                p.status_display_type,
+                &STATUS_DISPLAY_TYPES,
+            )?,
+            start_timestamp: p.start_timestamp,
+            end_timestamp: p.end_timestamp,
+            buttons: normalize_buttons(p.buttons)?,
+            party_id: non_empty(p.party_id).map(|id| truncate_graphemes(&id, TEXT_MAX_CHARS)),
+            party_size: p.party_size,
+        })
+    }
+
+    /// Собрать JSON активности: ошибка проверки — InvalidPresence, сбой сериализации — Internal
+    pub fn build(self) -> Result<Value, RpcError> {
+        let p = self.normalize()?;
+        let activity = to_activity(&p);
+        // Activity только заимствует строки Presence — в воркер уходит сериализованный JSON.
+        // Null означал бы очистку активности, поэтому ошибку не глотаем
+        serde_json::to_value(&activity)
+            .map_err(|e| RpcError::internal(format!("Failed to serialize activity: {}", e)))
+    }
+}
+
+/// Собрать activity::Activity из уже нормализованного Presence
+fn to_activity(p: &Presence) -> activity::Activity<'_> {
+    let mut activity = activity::Activity::new();
+    if let Some(name) = &p.name {
+        activity = activity.name(name);
+    }
+    if let Some(state) = &p.state {
+        activity = activity.state(state);
+    }
+    if let Some(details) = &p.details {
+        activity = activity.details(details);
+    }
+
+    match p.activity_type.as_deref() {
+        Some("playing") => activity = activity.activity_type(activity::ActivityType::Playing),
+        Some("listening") => activity = activity.activity_type(activity::ActivityType::Listening),
+        Some("watching") => activity = activity.activity_type(activity::ActivityType::Watching),
+        Some("competing") => activity = activity.activity_type(activity::ActivityType::Competing),
+        _ => {}
+    }
+
+    match p.status_display_type.as_deref() {
+        Some("name") => activity = activity.status_display_type(activity::StatusDisplayType::Name),
+        Some("state") => activity = activity.status_display_type(activity::StatusDisplayType::State),
+        Some("details") => {
+            activity = activity.status_display_type(activity::StatusDisplayType::Details)
+        }
+        _ => {}
+    }
+
+    if p.start_timestamp.is_some() || p.end_timestamp.is_some() {
+        let mut timestamps = activity::Timestamps::new();
+        if let Some(start) = p.start_timestamp {
+            timestamps = timestamps.start(start);
+        }
+        if let Some(end) = p.end_timestamp {
+            timestamps = timestamps.end(end);
+        }
+        activity = activity.timestamps(timestamps);
+    }
+
+    if p.large_image.is_some() || p.small_image.is_some() || p.large_text.is_some() || p.small_text.is_some() {
+        let mut assets = activity::Assets::new();
+        if let Some(large_image) = &p.large_image {
+            assets = assets.large_image(large_image);
+        }
+        if let Some(small_image) = &p.small_image {
+            assets = assets.small_image(small_image);
+        }
+        if let Some(large_text) = &p.large_text {
+            assets = assets.large_text(large_text);
+        }
+        if let Some(small_text) = &p.small_text {
+            assets = assets.small_text(small_text);
+        }
+        activity = activity.assets(assets);
+    }
+
+    if !p.buttons.is_empty() {
+        activity = activity.buttons(
+            p.buttons
+                .iter()
+                .map(|b| activity::Button::new(&b.label, &b.url))
+                .collect(),
+        );
+    }
+
+    if p.party_id.is_some() || p.party_size.is_some() {
+        let mut party = activity::Party::new();
+        if let Some(id) = &p.party_id {
+            party = party.id(id);
+        }
+        if let Some(size) = p.party_size {
+            party = party.size(size);
+        }
+        activity = activity.party(party);
+    }
+
+    activity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use serde_json::json;
+
+    fn normalize(presence: Presence) -> Result<Presence, PresenceError> {
+        PresenceBuilder::new(presence).normalize()
+    }
+
+    fn button(label: &str, url: &str) -> PresenceButton {
+        PresenceButton {
+            label: label.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    /// Ошибка в том виде, в каком её видит фронтенд (RpcError.details)
+    fn error_json(err: PresenceError) -> serde_json::Value {
+        serde_json::to_value(err).unwrap()
+    }
+
+    #[test]
+    fn truncate_keeps_short_text() {
+        assert_eq!(truncate_graphemes("abcd", 4), "abcd");
+        assert_eq!(truncate_graphemes("", 4), "");
+    }
+
+    #[test]
+    fn truncate_marks_cut_with_ellipsis() {
+        assert_eq!(truncate_graphemes("abcdef", 4), "abc…");
+        assert_eq!(truncate_graphemes("abcdef", 4).chars().count(), 4);
+    }
+
+    #[test]
+    fn truncate_does_not_split_graphemes() {
+        // e + комбинирующий акут — одна графема из двух символов
+        assert_eq!(truncate_graphemes("abe\u{301}cd", 4), "ab…");
+        // Семья из трёх эмодзи через ZWJ — пять символов
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let text = format!("{}{}", "a".repeat(126), family);
+        assert_eq!(truncate_graphemes(&text, TEXT_MAX_CHARS), format!("{}…", "a".repeat(126)));
+    }
+
+    #[test]
+    fn long_text_fields_are_truncated() {
+        let p = normalize(Presence {
+            details: Some("x".repeat(200)),
+            ..Presence::default()
+        })
+        .unwrap();
+        let details = p.details.unwrap();
+        assert_eq!(details.chars().count(), TEXT_MAX_CHARS);
+        assert!(details.ends_with('…'));
+    }
+
+    #[test]
+    fn text_shorter_than_two_chars_is_rejected() {
+        let err = PresenceBuilder::new(Presence {
+            state: Some("a".to_string()),
+            ..Presence::default()
+        })
+        .build()
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidPresence);
+        assert_eq!(
+            err.details,
+            Some(json!({ "code": "too_short", "field": "state", "min": 2, "actual": 1 }))
+        );
+
+        let p = normalize(Presence {
+            state: Some("ab".to_string()),
+            details: Some("   ".to_string()),
+            ..Presence::default()
+        })
+        .unwrap();
+        assert_eq!(p.state.as_deref(), Some("ab"));
+        // Пробельная строка — поле не задано, а не слишком короткое
+        assert_eq!(p.details, None);
+    }
+
+    #[test]
+    fn enums_are_case_folded() {
+        let p = normalize(Presence {
+            activity_type: Some("Watching".to_string()),
+            status_display_type: Some("DETAILS".to_string()),
+            ..Presence::default()
+        })
+        .unwrap();
+        assert_eq!(p.activity_type.as_deref(), Some("watching"));
+        assert_eq!(p.status_display_type.as_deref(), Some("details"));
+    }
+
+    #[test]
+    fn unknown_enum_value_is_rejected() {
+        let err = normalize(Presence {
+            activity_type: Some("Streaming".to_string()),
+            ..Presence::default()
+        })
+        .unwrap_err();
+        match err {
+            PresenceError::InvalidEnum { field, value, expected } => {
+                assert_eq!(field, "activity_type");
+                assert_eq!(value, "Streaming");
+                assert_eq!(expected, ACTIVITY_TYPES);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn at_most_two_buttons() {
+        let buttons = vec![
+            button("Site", "https://example.com"),
+            button("Docs", "https://example.com/docs"),
+            button("Blog", "https://example.com/blog"),
+        ];
+        assert_eq!(
+            error_json(normalize_buttons(buttons).unwrap_err()),
+            json!({ "code": "too_many_buttons", "max": 2, "actual": 3 })
+        );
+    }
+
+    #[test]
+    fn button_url_must_be_short_http_url() {
+        let long = format!("https://example.com/{}", "a".repeat(BUTTON_URL_MAX_CHARS));
+        assert_eq!(
+            error_json(normalize_buttons(vec![button("Site", &long)]).unwrap_err()),
+            json!({
+                "code": "invalid_url",
+                "field": "buttons[0].url",
+                "reason": "must be at most 512 characters long"
+            })
+        );
+
+        let buttons = vec![
+            button("Site", "https://example.com"),
+            button("Mail", "mailto:me@example.com"),
+        ];
+        assert_eq!(
+            error_json(normalize_buttons(buttons).unwrap_err()),
+            json!({ "code": "invalid_url", "field": "buttons[1].url", "reason": "must be an http(s) URL" })
+        );
+    }
+
+    #[test]
+    fn button_label_is_required_and_truncated() {
+        assert_eq!(
+            error_json(normalize_buttons(vec![button("   ", "https://example.com")]).unwrap_err()),
+            json!({ "code": "too_short", "field": "buttons[0].label", "min": 1, "actual": 0 })
+        );
+
+        let buttons = normalize_buttons(vec![button(
+            &format!("  {}  ", "L".repeat(40)),
+            "http://example.com",
+        )])
+        .unwrap();
+        assert_eq!(buttons[0].label, format!("{}…", "L".repeat(BUTTON_LABEL_MAX_CHARS - 1)));
+        assert_eq!(buttons[0].url, "http://example.com");
+    }
+
+    #[test]
+    fn party_size_needs_one_to_max_members() {
+        assert!(validate_party_size(None).is_ok());
+        assert!(validate_party_size(Some([1, 1])).is_ok());
+        assert!(validate_party_size(Some([2, 4])).is_ok());
+        for size in [[0, 1], [3, 2]] {
+            match validate_party_size(Some(size)).unwrap_err() {
+                PresenceError::InvalidParty { reason } => {
+                    assert!(reason.ends_with(&format!("got [{}, {}]", size[0], size[1])), "{}", reason)
+                }
+                other => panic!("unexpected error: {}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,761 @@
+// src/presence_engine.rs
+// Движок активности: сам опрашивает окна, выбирает окно для активности и обновляет RPC.
+// Раньше это делал webview по setInterval, и активность «замерзала», когда окно
+// свёрнуто или webview притормаживается. Теперь UI только показывает состояние
+// (событие presence-engine:state) и управляет движком командами select/pause/resume.
+// Изменения окон приходят от window_watcher; полный опрос остаётся редкой страховкой
+// (и основным способом, если наблюдатель недоступен).
+use crate::app_registry;
+use crate::discord_ipc::InstanceSelector;
+use crate::error::{ErrorCode, RpcError};
+use crate::presence::{Presence, PresenceBuilder};
+use crate::presence_template::{PresenceTemplate, ProfileInfo, TemplateVars};
+use crate::rpc_pool::RpcPool;
+use crate::rpc_worker::{wait_until, BackoffConfig};
+use crate::window_focus::{FocusTracker, SelectionPolicy};
+use crate::window_provider::WindowProvider;
+use crate::window_watcher::{self, WindowEvent};
+use crate::windows_api::{self, WindowInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+pub const EVENT_STATE: &str = "presence-engine:state";
+
+/// Сколько копить события окон, прежде чем перечитать окна (заголовки меняются пачками)
+const INSPECT_DEBOUNCE: Duration = Duration::from_millis(150);
+/// Полный опрос при работающем наблюдателе — только чтобы не пропустить потерянное событие
+const WATCHED_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Настройки, которые UI хранит в config.json и передаёт движку
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineSettings {
+    /// Период полного опроса окон; 0 — только по refresh_windows и событиям наблюдателя
+    pub poll_interval_ms: u64,
+    pub activity_type: String,
+    pub instance: InstanceSelector,
+    /// Своя активность у каждого найденного приложения (отдельное соединение на app_id)
+    pub concurrent: bool,
+    /// Какое окно выбирать, пока пользователь не закрепил окно сам
+    pub selection: SelectionPolicy,
+    /// Имя и @handle для {user} / {handle} в шаблонах активности
+    pub profile: ProfileInfo,
+}
+
+impl Default for EngineSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 5_000,
+            activity_type: "playing".to_string(),
+            instance: InstanceSelector::Any,
+            concurrent: false,
+            selection: SelectionPolicy::default(),
+            profile: ProfileInfo::default(),
+        }
+    }
+}
+
+/// Снимок состояния движка для UI
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineState {
+    /// Найденные окна поддерживаемых приложений
+    pub windows: Vec<WindowInfo>,
+    /// Окно, чья активность показывается в Discord
+    pub selected: Option<WindowInfo>,
+    /// Окно в фокусе (любое, не только из `windows`; None — неизвестно)
+    pub focused: Option<isize>,
+    /// На паузе активность не показывается, но окна продолжают опрашиваться
+    pub paused: bool,
+    /// Время последнего опроса окон (unix-время в миллисекундах)
+    pub last_check_ms: Option<u64>,
+    pub settings: EngineSettings,
+}
+
+/// Куда движок отдаёт снимки состояния; в приложении — в Tauri
+pub type StateSink = Arc<dyn Fn(&EngineState) + Send + Sync>;
+
+pub fn tauri_sink(app: AppHandle) -> StateSink {
+    Arc::new(move |state| {
+        if let Err(e) = app.emit(EVENT_STATE, state) {
+            eprintln!("Failed to emit {}: {}", EVENT_STATE, e);
+        }
+    })
+}
+
+type Reply<T> = oneshot::Sender<Result<T, RpcError>>;
+
+/// Как выбрано окно активности
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    /// По политике из настроек
+    Auto,
+    /// Закреплено пользователем, пока окно открыто
+    Pinned(isize),
+    /// Пользователь снял выбор — активности нет
+    Off,
+}
+
+enum EngineCommand {
+    Select {
+        hwnd: Option<isize>,
+        reply: Reply<()>,
+    },
+    Pause,
+    Resume,
+    Configure(EngineSettings),
+    Refresh {
+        reply: Reply<EngineState>,
+    },
+}
+
+/// Доступ к движку для команд Tauri: сам движок работает в своей задаче,
+/// команды уходят ему в очередь, а состояние читается из последнего снимка
+#[derive(Clone)]
+pub struct PresenceEngine {
+    tx: mpsc::UnboundedSender<EngineCommand>,
+    state: watch::Receiver<EngineState>,
+}
+
+impl PresenceEngine {
+    pub fn spawn(pool: Arc<RpcPool>, provider: Arc<dyn WindowProvider>, sink: StateSink) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let engine = Engine::new(pool, provider, Some(window_watcher::spawn()), sink);
+        let state = engine.state.subscribe();
+        tauri::async_runtime::spawn(engine.run(rx));
+        Self { tx, state }
+    }
+
+    pub fn state(&self) -> EngineState {
+        self.state.borrow().clone()
+    }
+
+    /// Полный опрос окон вне расписания
+    pub async fn refresh(&self) -> Result<EngineState, RpcError> {
+        self.request(|reply| EngineCommand::Refresh { reply }).await
+    }
+
+    fn send(&self, cmd: EngineCommand) -> Result<(), RpcError> {
+        self.tx
+            .send(cmd)
+            .map_err(|_| RpcError::worker_unavailable("Presence engine is not running"))
+    }
+
+    async fn request<T>(
+        &self,
+        make: impl FnOnce(Reply<T>) -> EngineCommand,
+    ) -> Result<T, RpcError> {
+        let (reply, rx) = oneshot::channel();
+        self.send(make(reply))?;
+        rx.await
+            .map_err(|_| RpcError::worker_unavailable("Presence engine dropped the request"))?
+    }
+}
+
+struct Engine {
+    pool: Arc<RpcPool>,
+    provider: Arc<dyn WindowProvider>,
+    settings: EngineSettings,
+    /// Настройки уже пришли от UI. До этого окна опрашиваются, но к Discord не подключаемся:
+    /// с настройками по умолчанию можно выбрать не тот экземпляр или режим concurrent
+    configured: bool,
+    windows: Vec<WindowInfo>,
+    selected: Option<isize>,
+    selection: Selection,
+    focus: FocusTracker,
+    paused: bool,
+    last_check_ms: Option<u64>,
+    /// app_id, для которых движок открыл соединения в пуле
+    connected: HashSet<String>,
+    next_poll: Option<Instant>,
+    /// None — наблюдатель не запустился или остановился, окна только опрашиваются
+    window_events: Option<mpsc::UnboundedReceiver<WindowEvent>>,
+    /// Окна из событий, которые нужно перечитать
+    dirty: HashSet<isize>,
+    inspect_at: Option<Instant>,
+    state: watch::Sender<EngineState>,
+    sink: StateSink,
+}
+
+impl Engine {
+    fn new(
+        pool: Arc<RpcPool>,
+        provider: Arc<dyn WindowProvider>,
+        window_events: Option<mpsc::UnboundedReceiver<WindowEvent>>,
+        sink: StateSink,
+    ) -> Self {
+        let settings = EngineSettings::default();
+        let (state, _) = watch::channel(EngineState {
+            windows: Vec::new(),
+            selected: None,
+            focused: None,
+            paused: false,
+            last_check_ms: None,
+            settings: settings.clone(),
+        });
+        Self {
+            pool,
+            provider,
+            settings,
+            configured: false,
+            windows: Vec::new(),
+            selected: None,
+            selection: Selection::Auto,
+            focus: FocusTracker::default(),
+            paused: false,
+            last_check_ms: None,
+            connected: HashSet::new(),
+            next_poll: Some(Instant::now()),
+            window_events,
+            dirty: HashSet::new(),
+            inspect_at: None,
+            state,
+            sink,
+        }
+    }
+
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<EngineCommand>) {
+        loop {
+            let next_poll = self.next_poll;
+            let inspect_at = self.inspect_at;
+            let watching = self.window_events.is_some();
+            tokio::select! {
+                cmd = rx.recv() => match cmd {
+                    Some(cmd) => self.handle(cmd).await,
+                    None => break,
+                },
+                event = next_window_event(&mut self.window_events), if watching => match event {
+                    Some(event) => self.on_window_event(event).await,
+                    None => {
+                        eprintln!("Window watcher is unavailable, falling back to polling");
+                        self.window_events = None;
+                        self.schedule_poll();
+                    }
+                },
+                _ = wait_until(inspect_at), if inspect_at.is_some() => self.inspect_dirty().await,
+                _ = wait_until(next_poll), if next_poll.is_some() => self.poll().await,
+            }
+        }
+        self.disconnect_all().await;
+    }
+
+    async fn handle(&mut self, cmd: EngineCommand) {
+        match cmd {
+            EngineCommand::Select { hwnd, reply } => {
+                if let Some(hwnd) = hwnd {
+                    if !self.windows.iter().any(|w| w.hwnd == hwnd) {
+                        let _ = reply.send(Err(RpcError::new(
+                            ErrorCode::WindowNotFound,
+                            format!("Window {} is not in the list of detected windows", hwnd),
+                        )));
+                        return;
+                    }
+                }
+                self.selection = hwnd.map_or(Selection::Off, Selection::Pinned);
+                let _ = reply.send(Ok(()));
+                self.sync().await;
+            }
+            EngineCommand::Pause => {
+                self.paused = true;
+                self.sync().await;
+            }
+            EngineCommand::Resume => {
+                self.paused = false;
+                self.sync().await;
+            }
+            EngineCommand::Configure(settings) => {
+                if self.configured && settings == self.settings {
+                    return;
+                }
+                self.configured = true;
+                // Другой экземпляр Discord или схема соединений — переподключаемся с нуля
+                if settings.instance != self.settings.instance
+                    || settings.concurrent != self.settings.concurrent
+                {
+                    self.disconnect_all().await;
+                }
+                let reschedule = settings.poll_interval_ms != self.settings.poll_interval_ms;
+                // Выбрали другую политику — закреплённое вручную окно больше не держим
+                if settings.selection != self.settings.selection {
+                    self.selection = Selection::Auto;
+                }
+                self.settings = settings;
+                if reschedule {
+                    self.schedule_poll();
+                }
+                self.sync().await;
+            }
+            EngineCommand::Refresh { reply } => {
+                self.poll().await;
+                let _ = reply.send(Ok(self.state.borrow().clone()));
+            }
+        }
+    }
+
+    /// Опросить окна и привести активность в соответствие с ними
+    async fn poll(&mut self) {
+        let provider = self.provider.clone();
+        match tokio::task::spawn_blocking(move || windows_api::detect_windows(&*provider)).await {
+            Ok(windows) => {
+                self.windows = windows.into_iter().filter(is_listed).collect();
+                self.last_check_ms = Some(now_ms());
+                // Без наблюдателя фокус узнаём только при опросе (с ним — до первого события)
+                if self.window_events.is_none() || self.focus.focused().is_none() {
+                    let focused = self.windows.iter().find(|w| w.focused).map(|w| w.hwnd);
+                    self.focus.set_focused(focused, now_ms());
+                }
+                let windows = &self.windows;
+                self.focus
+                    .retain(|hwnd| windows.iter().any(|w| w.hwnd == hwnd));
+                // Полный список уже свежий
+                self.dirty.clear();
+                self.inspect_at = None;
+            }
+            Err(e) => eprintln!("Window polling failed: {}", e),
+        }
+        self.schedule_poll();
+        self.sync().await;
+    }
+
+    fn schedule_poll(&mut self) {
+        let interval = Duration::from_millis(self.settings.poll_interval_ms);
+        self.next_poll = match interval {
+            Duration::ZERO => None,
+            _ if self.window_events.is_some() => {
+                Some(Instant::now() + interval.max(WATCHED_POLL_INTERVAL))
+            }
+            _ => Some(Instant::now() + interval),
+        };
+    }
+
+    async fn on_window_event(&mut self, event: WindowEvent) {
+        match event {
+            WindowEvent::Destroyed(hwnd) => {
+                self.dirty.remove(&hwnd);
+                let before = self.windows.len();
+                self.windows.retain(|w| w.hwnd != hwnd);
+                if self.windows.len() != before {
+                    self.sync().await;
+                }
+            }
+            WindowEvent::Created(hwnd) | WindowEvent::TitleChanged(hwnd) => {
+                self.dirty.insert(hwnd);
+                self.inspect_at
+                    .get_or_insert_with(|| Instant::now() + INSPECT_DEBOUNCE);
+            }
+            WindowEvent::FocusChanged(hwnd) => {
+                if self.focus.set_focused(hwnd, now_ms()) {
+                    self.sync().await;
+                }
+            }
+        }
+    }
+
+    /// Перечитать только окна из событий — без полного перечисления
+    async fn inspect_dirty(&mut self) {
+        self.inspect_at = None;
+        let dirty: Vec<isize> = self.dirty.drain().collect();
+        let focused = self.focus.focused();
+        let provider = self.provider.clone();
+        let inspected = tokio::task::spawn_blocking(move || {
+            dirty
+                .into_iter()
+                .map(|hwnd| {
+                    let info =
+                        windows_api::inspect_window(&*provider, hwnd, focused).filter(is_listed);
+                    (hwnd, info)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+        let inspected = match inspected {
+            Ok(inspected) => inspected,
+            Err(e) => {
+                eprintln!("Window inspection failed: {}", e);
+                return;
+            }
+        };
+
+        let mut changed = false;
+        for (hwnd, info) in inspected {
+            let pos = self.windows.iter().position(|w| w.hwnd == hwnd);
+            match (pos, info) {
+                (Some(i), Some(info)) if self.windows[i] != info => self.windows[i] = info,
+                (Some(i), None) => {
+                    self.windows.remove(i);
+                }
+                (None, Some(info)) => self.windows.push(info),
+                _ => continue,
+            }
+            changed = true;
+        }
+        if changed {
+            self.last_check_ms = Some(now_ms());
+            self.sync().await;
+        }
+    }
+
+    /// Пересчитать выбор, обновить RPC и отдать состояние UI
+    async fn sync(&mut self) {
+        self.selected = match self.selection {
+            Selection::Off => None,
+            Selection::Pinned(hwnd) if self.windows.iter().any(|w| w.hwnd == hwnd) => Some(hwnd),
+            // Закреплённое окно закрылось — снова выбираем по политике
+            _ => {
+                self.selection = Selection::Auto;
+                self.settings
+                    .selection
+                    .choose(&self.windows, &self.focus, self.selected)
+            }
+        };
+
+        if self.paused || !self.configured {
+            self.disconnect_all().await;
+        } else if self.settings.concurrent {
+            self.sync_concurrent().await;
+        } else {
+            self.sync_single().await;
+        }
+        self.publish();
+    }
+
+    /// Одно соединение — для приложения выбранного окна
+    async fn sync_single(&mut self) {
+        let Some((win, app_id)) = self
+            .selected_window()
+            .and_then(|w| Some((w.clone(), self.app_id(w)?)))
+        else {
+            self.clear_unselected().await;
+            return;
+        };
+
+        if !self.connected.contains(&app_id) {
+            self.disconnect_all().await;
+            self.connect(&win, &app_id, false).await;
+        }
+        self.set_activity(&win, &app_id).await;
+    }
+
+    /// Ничего не выбрано (например, фокус ушёл в браузер): пока окна приложения открыты,
+    /// только очищаем активность — соединение понадобится, когда окно снова выберут
+    async fn clear_unselected(&mut self) {
+        let open: HashSet<String> = self.windows.iter().filter_map(|w| self.app_id(w)).collect();
+        let connected: Vec<String> = self.connected.iter().cloned().collect();
+        for app_id in connected {
+            if !open.contains(&app_id) {
+                self.connected.remove(&app_id);
+                let _ = self.pool.close(Some(&app_id)).await;
+                continue;
+            }
+            let res = match self.pool.worker(Some(&app_id)) {
+                Ok(worker) => worker.clear().await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                eprintln!("Failed to clear activity for {}: {}", app_id, e);
+                if e.code == ErrorCode::NotInitialized {
+                    self.connected.remove(&app_id);
+                }
+            }
+        }
+    }
+
+    /// Соединение на каждое найденное приложение; у выбранного окна приоритет в своём приложении
+    async fn sync_concurrent(&mut self) {
+        let mut targets: Vec<(WindowInfo, String)> = Vec::new();
+        let ordered = self.selected_window().into_iter().chain(
+            self.windows
+                .iter()
+                .filter(|w| Some(w.hwnd) != self.selected),
+        );
+        for win in ordered {
+            if let Some(app_id) = self.app_id(win) {
+                if !targets.iter().any(|(_, id)| *id == app_id) {
+                    targets.push((win.clone(), app_id));
+                }
+            }
+        }
+
+        let gone: Vec<String> = self
+            .connected
+            .iter()
+            .filter(|id| !targets.iter().any(|(_, t)| t == *id))
+            .cloned()
+            .collect();
+        for app_id in gone {
+            self.connected.remove(&app_id);
+            let _ = self.pool.close(Some(&app_id)).await;
+        }
+
+        for (win, app_id) in targets {
+            if !self.connected.contains(&app_id) {
+                self.connect(&win, &app_id, true).await;
+            }
+            self.set_activity(&win, &app_id).await;
+        }
+    }
+
+    async fn connect(&mut self, win: &WindowInfo, app_id: &str, concurrent: bool) {
+        // Даже при ошибке воркер остаётся в пуле и переподключается в фоне,
+        // а активность уйдёт, как только соединение появится
+        if let Err(e) = self
+            .pool
+            .connect(
+                app_id.to_string(),
+                self.settings.instance.clone(),
+                BackoffConfig::default(),
+                win.pid,
+                concurrent,
+            )
+            .await
+        {
+            eprintln!("Failed to init RPC for {}: {}", win.display_name, e);
+        } else {
+            println!("RPC initialized: {}", win.display_name);
+        }
+        self.connected.insert(app_id.to_string());
+    }
+
+    async fn set_activity(&mut self, win: &WindowInfo, app_id: &str) {
+        let template = app_registry::current()
+            .find(&win.process_name)
+            .and_then(|app| app.config.presence.clone())
+            .unwrap_or_default()
+            .or(PresenceTemplate::builtin());
+        let now = (now_ms() / 1000) as i64;
+        let vars =
+            TemplateVars::for_window(win, &self.settings.profile, self.pool.user().as_ref(), now);
+        let presence = Presence {
+            activity_type: Some(self.settings.activity_type.clone()),
+            start_timestamp: win.process_start_time,
+            ..template.render(&vars)
+        };
+        let res = match PresenceBuilder::new(presence).build() {
+            Ok(activity) => match self.pool.worker(Some(app_id)) {
+                Ok(worker) => worker.set_activity(activity).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            eprintln!("RPC update failed for {}: {}", win.display_name, e);
+            // Пул сам закрыл соединение (процесс завершился) — на следующем опросе откроем заново
+            if e.code == ErrorCode::NotInitialized {
+                self.connected.remove(app_id);
+            }
+        }
+    }
+
+    async fn disconnect_all(&mut self) {
+        for app_id in self.connected.drain() {
+            let _ = self.pool.close(Some(&app_id)).await;
+        }
+    }
+
+    fn selected_window(&self) -> Option<&WindowInfo> {
+        let hwnd = self.selected?;
+        self.windows.iter().find(|w| w.hwnd == hwnd)
+    }
+
+    fn app_id(&self, win: &WindowInfo) -> Option<String> {
+        app_registry::current().app_id(&win.process_name)
+    }
+
+    fn publish(&self) {
+        let annotate = |win: &WindowInfo| {
+            let mut win = win.clone();
+            self.focus.annotate(&mut win);
+            win
+        };
+        let state = EngineState {
+            windows: self.windows.iter().map(annotate).collect(),
+            selected: self.selected_window().map(annotate),
+            focused: self.focus.focused(),
+            paused: self.paused,
+            last_check_ms: self.last_check_ms,
+            settings: self.settings.clone(),
+        };
+        (self.sink)(&state);
+        self.state.send_replace(state);
+    }
+}
+
+/// В списке только окна, из заголовка которых удалось достать имя документа
+fn is_listed(win: &WindowInfo) -> bool {
+    !win.document_name.trim().is_empty()
+}
+
+async fn next_window_event(
+    events: &mut Option<mpsc::UnboundedReceiver<WindowEvent>>,
+) -> Option<WindowEvent> {
+    match events {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Текущее состояние движка (при запуске UI, до первого события)
+#[tauri::command]
+pub fn get_presence_state(engine: State<'_, PresenceEngine>) -> EngineState {
+    engine.state()
+}
+
+/// Показывать активность этого окна; без `hwnd` — снять выбор и очистить активность
+#[tauri::command]
+pub async fn select_window(
+    engine: State<'_, PresenceEngine>,
+    hwnd: Option<isize>,
+) -> Result<(), RpcError> {
+    engine
+        .request(|reply| EngineCommand::Select { hwnd, reply })
+        .await
+}
+
+#[tauri::command]
+pub fn pause_presence(engine: State<'_, PresenceEngine>) -> Result<(), RpcError> {
+    engine.send(EngineCommand::Pause)
+}
+
+#[tauri::command]
+pub fn resume_presence(engine: State<'_, PresenceEngine>) -> Result<(), RpcError> {
+    engine.send(EngineCommand::Resume)
+}
+
+/// Интервал опроса, тип активности, экземпляр Discord и режим concurrent из настроек UI
+#[tauri::command]
+pub fn configure_presence(
+    engine: State<'_, PresenceEngine>,
+    settings: EngineSettings,
+) -> Result<(), RpcError> {
+    engine.send(EngineCommand::Configure(settings))
+}
+
+/// Опросить окна сейчас, не дожидаясь интервала («Check Status»)
+#[tauri::command]
+pub async fn refresh_windows(engine: State<'_, PresenceEngine>) -> Result<EngineState, RpcError> {
+    engine.refresh().await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::discord_rpc::tests::worker_server;
+    use crate::fake_windows::{FakeWindowProvider, Fixture};
+
+    fn fixture() -> Fixture {
+        let json = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/windows.json"));
+        Fixture::from_json(json).unwrap()
+    }
+
+    /// Движок без наблюдателя окон: тест сам вызывает опрос и события
+    fn engine(provider: Arc<FakeWindowProvider>) -> Engine {
+        Engine::new(RpcPool::new(Arc::new(|_| {})), provider, None, Arc::new(|_| {}))
+    }
+
+    async fn configured_engine(provider: Arc<FakeWindowProvider>) -> Engine {
+        let mut engine = engine(provider);
+        engine
+            .handle(EngineCommand::Configure(EngineSettings::default()))
+            .await;
+        engine.poll().await;
+        engine
+    }
+
+    async fn select(engine: &mut Engine, hwnd: Option<isize>) -> Result<(), RpcError> {
+        let (reply, rx) = oneshot::channel();
+        engine.handle(EngineCommand::Select { hwnd, reply }).await;
+        rx.await.unwrap()
+    }
+
+    fn app_id(process_name: &str) -> Option<String> {
+        app_registry::current().app_id(process_name)
+    }
+
+    fn connected_apps(engine: &Engine) -> Vec<Option<String>> {
+        engine.pool.statuses().into_iter().map(|s| s.app_id).collect()
+    }
+
+    #[tokio::test]
+    async fn connects_only_after_first_configure() {
+        let (_env, server) = worker_server("engine-configure").await;
+        let mut engine = engine(Arc::new(FakeWindowProvider::new(fixture())));
+
+        engine.poll().await;
+        let state = engine.state.borrow().clone();
+        let hwnds: Vec<isize> = state.windows.iter().map(|w| w.hwnd).collect();
+        assert_eq!(hwnds, vec![1001, 1002]);
+        assert_eq!(state.selected.map(|w| w.hwnd), Some(1001));
+        assert!(connected_apps(&engine).is_empty());
+        assert!(server.received().is_empty());
+
+        // Значения по умолчанию, присланные UI явно, тоже включают движок
+        engine
+            .handle(EngineCommand::Configure(EngineSettings::default()))
+            .await;
+        assert_eq!(connected_apps(&engine), vec![app_id("Cinema 4D.exe")]);
+        assert_eq!(server.activities().last().unwrap()["state"], "scene_final.c4d *");
+    }
+
+    #[tokio::test]
+    async fn selecting_a_window_switches_the_connection() {
+        let (_env, server) = worker_server("engine-select").await;
+        let mut engine = configured_engine(Arc::new(FakeWindowProvider::new(fixture()))).await;
+
+        select(&mut engine, Some(1002)).await.unwrap();
+        // Без concurrent соединение Cinema 4D закрывается
+        assert_eq!(connected_apps(&engine), vec![app_id("AfterFX.exe")]);
+        assert_eq!(server.activities().last().unwrap()["state"], "/Projects/intro.aep");
+
+        // Блокнота нет в реестре — выбрать его нельзя
+        let err = select(&mut engine, Some(1003)).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::WindowNotFound);
+        assert_eq!(engine.state.borrow().selected.as_ref().map(|w| w.hwnd), Some(1002));
+    }
+
+    #[tokio::test]
+    async fn window_events_update_the_activity() {
+        let (_env, server) = worker_server("engine-events").await;
+        let provider = Arc::new(FakeWindowProvider::new(fixture()));
+        let mut engine = configured_engine(provider.clone()).await;
+
+        let mut renamed = fixture();
+        renamed.windows[0].title = "Cinema 4D 2024.1.0 - [scene_v2.c4d] - Main".to_string();
+        provider.load(renamed);
+        engine.on_window_event(WindowEvent::TitleChanged(1001)).await;
+        assert!(engine.inspect_at.is_some());
+        engine.inspect_dirty().await;
+        assert_eq!(engine.state.borrow().windows[0].document_name, "scene_v2.c4d");
+        assert_eq!(server.activities().last().unwrap()["state"], "scene_v2.c4d");
+
+        // Выбранное окно закрылось — политика First берёт следующее
+        engine.on_window_event(WindowEvent::Destroyed(1001)).await;
+        assert_eq!(connected_apps(&engine), vec![app_id("AfterFX.exe")]);
+        assert_eq!(server.activities().last().unwrap()["state"], "/Projects/intro.aep");
+    }
+
+    #[tokio::test]
+    async fn pause_closes_connections_until_resume() {
+        let (_env, server) = worker_server("engine-pause").await;
+        let mut engine = configured_engine(Arc::new(FakeWindowProvider::new(fixture()))).await;
+        assert_eq!(connected_apps(&engine), vec![app_id("Cinema 4D.exe")]);
+
+        engine.handle(EngineCommand::Pause).await;
+        assert!(connected_apps(&engine).is_empty());
+        assert!(engine.state.borrow().paused);
+
+        engine.handle(EngineCommand::Resume).await;
+        assert_eq!(connected_apps(&engine), vec![app_id("Cinema 4D.exe")]);
+        assert_eq!(server.activities().len(), 2);
+    }
+}
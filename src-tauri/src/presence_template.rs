@@ -0,0 +1,391 @@
+// src/presence_template.rs
+// Шаблоны текста активности по приложениям (поле `presence` в реестре приложений):
+// `details: "Editing {file}"`, `state: "{app} {version}"`. Плейсхолдер — имя переменной;
+// `{file|document|title}` берёт первую непустую, `{version|"latest"}` — запасной текст.
+// `{{` и `}}` — литеральные скобки. Поле, которое отрендерилось в пустую строку, в активность
+// не попадает.
+use crate::discord_ipc::DiscordUser;
+use crate::presence::{Presence, TEXT_MIN_CHARS};
+use crate::windows_api::WindowInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Текст полей активности; None — поле берётся из встроенного шаблона
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceTemplate {
+    pub details: Option<String>,
+    pub state: Option<String>,
+    pub large_image: Option<String>,
+    pub large_text: Option<String>,
+    pub small_image: Option<String>,
+    pub small_text: Option<String>,
+}
+
+impl PresenceTemplate {
+    /// Активность для приложений без своего шаблона: имя документа (или заголовок) и иконки
+    pub fn builtin() -> Self {
+        Self {
+            state: Some("{document|title}".to_string()),
+            large_image: Some("appicon".to_string()),
+            small_image: Some("fileicon".to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Незаданные поля — из `fallback`
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            details: self.details.or(fallback.details),
+            state: self.state.or(fallback.state),
+            large_image: self.large_image.or(fallback.large_image),
+            large_text: self.large_text.or(fallback.large_text),
+            small_image: self.small_image.or(fallback.small_image),
+            small_text: self.small_text.or(fallback.small_text),
+        }
+    }
+
+    /// Поля с именами, как в apps.json (для ошибок проверки)
+    pub fn fields(&self) -> [(&'static str, Option<&str>); 6] {
+        [
+            ("details", self.details.as_deref()),
+            ("state", self.state.as_deref()),
+            ("large_image", self.large_image.as_deref()),
+            ("large_text", self.large_text.as_deref()),
+            ("small_image", self.small_image.as_deref()),
+            ("small_text", self.small_text.as_deref()),
+        ]
+    }
+
+    /// Текстовые поля активности; шаблон с ошибкой пропускается с записью в лог.
+    /// Текст короче TEXT_MIN_CHARS (скажем, «{version}» из одной цифры) тоже опускается:
+    /// Discord его не примет, и PresenceBuilder отверг бы из-за него всю активность.
+    pub fn render(&self, vars: &TemplateVars) -> Presence {
+        let field = |name: &str, template: &Option<String>, min_chars: usize| {
+            let template = template.as_deref()?;
+            match render(template, vars) {
+                Ok(text) if text.trim().chars().count() >= min_chars => Some(text),
+                Ok(_) => None,
+                Err(e) => {
+                    eprintln!("Presence template {} {:?}: {}", name, template, e);
+                    None
+                }
+            }
+        };
+        Presence {
+            details: field("details", &self.details, TEXT_MIN_CHARS),
+            state: field("state", &self.state, TEXT_MIN_CHARS),
+            large_image: field("large_image", &self.large_image, 1),
+            large_text: field("large_text", &self.large_text, TEXT_MIN_CHARS),
+            small_image: field("small_image", &self.small_image, 1),
+            small_text: field("small_text", &self.small_text, TEXT_MIN_CHARS),
+            ..Presence::default()
+        }
+    }
+}
+
+/// Профиль из настроек UI; пустые поля заменяются данными Discord из READY
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileInfo {
+    pub display_name: String,
+    pub handle: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    /// Позиция в символах от начала шаблона
+    pub position: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    /// Варианты по порядку: первый непустой
+    Placeholder(Vec<Alternative>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Alternative {
+    Var(String),
+    Literal(String),
+}
+
+fn parse(template: &str) -> Result<Vec<Segment>, TemplateError> {
+    let chars: Vec<char> = template.chars().collect();
+    let error = |position: usize, reason: &str| TemplateError {
+        position,
+        reason: reason.to_string(),
+    };
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '{' if chars.get(i + 1) == Some(&'{') => {
+                text.push('{');
+                i += 2;
+            }
+            '}' if chars.get(i + 1) == Some(&'}') => {
+                text.push('}');
+                i += 2;
+            }
+            '}' => return Err(error(i, "Unmatched '}' (use '}}' for a literal brace)")),
+            '{' => {
+                let start = i;
+                let end = chars[start..]
+                    .iter()
+                    .position(|&c| c == '}')
+                    .map(|n| start + n)
+                    .ok_or_else(|| error(start, "Unclosed '{'"))?;
+                let body: String = chars[start + 1..end].iter().collect();
+                let alternatives = body
+                    .split('|')
+                    .map(|alt| {
+                        parse_alternative(alt.trim()).ok_or_else(|| {
+                            error(start, &format!("Invalid placeholder {{{}}}", body))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Placeholder(alternatives));
+                i = end + 1;
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// Имя переменной (буквы, цифры, `_`) или текст в двойных кавычках
+fn parse_alternative(alt: &str) -> Option<Alternative> {
+    if let Some(literal) = alt
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        return Some(Alternative::Literal(literal.to_string()));
+    }
+    let is_name = !alt.is_empty() && alt.chars().all(|c| c.is_alphanumeric() || c == '_');
+    is_name.then(|| Alternative::Var(alt.to_string()))
+}
+
+/// Проверить шаблон без подстановки (apps.json, add_app / update_app)
+pub fn validate(template: &str) -> Result<(), TemplateError> {
+    parse(template).map(|_| ())
+}
+
+/// Подставить переменные; неизвестная переменная — пустая строка
+pub fn render(template: &str, vars: &TemplateVars) -> Result<String, TemplateError> {
+    let mut out = String::new();
+    for segment in parse(template)? {
+        match segment {
+            Segment::Text(text) => out.push_str(&text),
+            Segment::Placeholder(alternatives) => {
+                let value = alternatives.iter().find_map(|alt| match alt {
+                    Alternative::Var(name) => vars.get(name),
+                    Alternative::Literal(text) => Some(text.as_str()),
+                });
+                out.push_str(value.unwrap_or_default());
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Переменные шаблона; пустые значения не хранятся, чтобы `{a|b}` переходил к следующему варианту
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars(HashMap<String, String>);
+
+impl TemplateVars {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let value = value.into();
+        if !value.trim().is_empty() {
+            self.0.insert(name.into(), value);
+        }
+    }
+
+    /// Переменные окна: app / display_name, process_name, title, document, elapsed,
+    /// именованные группы заголовка (перекрывают встроенные), user и handle.
+    /// `now` — unix-время в секундах для elapsed.
+    pub fn for_window(
+        win: &WindowInfo,
+        profile: &ProfileInfo,
+        discord_user: Option<&DiscordUser>,
+        now: i64,
+    ) -> Self {
+        let mut vars = Self::default();
+        vars.set("app", win.display_name.as_str());
+        vars.set("display_name", win.display_name.as_str());
+        vars.set("process_name", win.process_name.as_str());
+        vars.set("title", win.title.as_str());
+        vars.set("document", win.document_name.as_str());
+        if let Some(start) = win.process_start_time {
+            vars.set("elapsed", format_elapsed(now - start));
+        }
+
+        let discord_name = discord_user.map(|u| u.global_name.as_deref().unwrap_or(&u.username));
+        vars.set(
+            "user",
+            non_empty(&profile.display_name)
+                .or(discord_name)
+                .unwrap_or_default(),
+        );
+        let discord_handle = discord_user.map(|u| format!("@{}", u.username));
+        vars.set(
+            "handle",
+            non_empty(&profile.handle)
+                .map(str::to_string)
+                .or(discord_handle)
+                .unwrap_or_default(),
+        );
+
+        for (name, value) in &win.fields {
+            vars.set(name.as_str(), value.as_str());
+        }
+        vars
+    }
+}
+
+fn non_empty(s: &str) -> Option<&str> {
+    (!s.trim().is_empty()).then_some(s)
+}
+
+/// "2h 05m", "17m"; отрицательное время (часы сбились) — пусто
+fn format_elapsed(secs: i64) -> String {
+    if secs < 0 {
+        return String::new();
+    }
+    let minutes = secs / 60;
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {:02}m", hours, minutes % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> TemplateVars {
+        let mut vars = TemplateVars::default();
+        for (name, value) in pairs {
+            vars.set(*name, *value);
+        }
+        vars
+    }
+
+    fn error_at(template: &str) -> (usize, String) {
+        let e = validate(template).unwrap_err();
+        (e.position, e.reason)
+    }
+
+    #[test]
+    fn double_braces_are_literal() {
+        let v = vars(&[("app", "Cinema 4D")]);
+        assert_eq!(render("{{app}}", &v).unwrap(), "{app}");
+        assert_eq!(render("{{{app}}}", &v).unwrap(), "{Cinema 4D}");
+        assert_eq!(render("a }} b {{", &v).unwrap(), "a } b {");
+    }
+
+    #[test]
+    fn first_non_empty_alternative_wins() {
+        let template = "{file|document|title}";
+        assert_eq!(render(template, &vars(&[("title", "Untitled")])).unwrap(), "Untitled");
+        assert_eq!(
+            render(template, &vars(&[("document", "intro.aep"), ("title", "Untitled")])).unwrap(),
+            "intro.aep"
+        );
+        // Пустое значение не сохраняется — переходим к следующему варианту
+        assert_eq!(
+            render(template, &vars(&[("file", "  "), ("title", "Untitled")])).unwrap(),
+            "Untitled"
+        );
+    }
+
+    #[test]
+    fn quoted_alternative_is_fallback_text() {
+        assert_eq!(render(r#"{version|"latest"}"#, &vars(&[])).unwrap(), "latest");
+        assert_eq!(render(r#"{version|"latest"}"#, &vars(&[("version", "2024")])).unwrap(), "2024");
+        assert_eq!(render(r#"v{ version | "n/a" }"#, &vars(&[])).unwrap(), "vn/a");
+    }
+
+    #[test]
+    fn unknown_variable_renders_empty() {
+        assert_eq!(render("Editing {file}", &vars(&[])).unwrap(), "Editing ");
+    }
+
+    #[test]
+    fn unclosed_brace_reports_its_position() {
+        assert_eq!(error_at("Editing {file"), (8, "Unclosed '{'".to_string()));
+        // Позиция в символах, а не в байтах
+        assert_eq!(error_at("Сцена {file").0, 6);
+        assert_eq!(error_at("{app} {"), (6, "Unclosed '{'".to_string()));
+    }
+
+    #[test]
+    fn stray_closing_brace_and_bad_placeholders() {
+        assert_eq!(error_at("a } b").0, 2);
+        assert!(error_at("a } b").1.starts_with("Unmatched '}'"));
+        assert_eq!(error_at("x {}"), (2, "Invalid placeholder {}".to_string()));
+        assert_eq!(error_at("{file name}"), (0, "Invalid placeholder {file name}".to_string()));
+        assert_eq!(error_at("{file|}").0, 0);
+    }
+
+    #[test]
+    fn empty_fields_are_left_out() {
+        let template = PresenceTemplate {
+            details: Some("Editing {file}".to_string()),
+            state: Some("{document}".to_string()),
+            small_text: Some("{oops".to_string()),
+            ..PresenceTemplate::default()
+        };
+        let presence = template.render(&vars(&[("file", "scene.c4d")]));
+        assert_eq!(presence.details.as_deref(), Some("Editing scene.c4d"));
+        assert_eq!(presence.state, None);
+        assert_eq!(presence.small_text, None);
+    }
+
+    #[test]
+    fn text_shorter_than_discord_minimum_is_left_out() {
+        let template = PresenceTemplate {
+            details: Some("{version}".to_string()),
+            state: Some(" {dirty} ".to_string()),
+            large_text: Some("v{version}".to_string()),
+            large_image: Some("{icon}".to_string()),
+            ..PresenceTemplate::default()
+        };
+        let v = vars(&[("version", "9"), ("dirty", "*"), ("icon", "c")]);
+        let presence = template.render(&v);
+        assert_eq!(presence.details, None);
+        assert_eq!(presence.state, None);
+        assert_eq!(presence.large_text.as_deref(), Some("v9"));
+        // Ключ картинки минимумом для текста не ограничен
+        assert_eq!(presence.large_image.as_deref(), Some("c"));
+
+        // Без коротких полей активность собирается, а не отвергается целиком
+        let activity = crate::presence::PresenceBuilder::new(presence).build().unwrap();
+        assert_eq!(activity["assets"]["large_text"], "v9");
+    }
+}
@@ -0,0 +1,105 @@
+// src/rate_limit.rs
+// Token bucket для SET_ACTIVITY: Discord пропускает примерно 5 обновлений за 20 секунд
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    /// Сколько токенов восстанавливается за секунду
+    refill_rate: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// `capacity` обновлений за окно `per`
+    pub fn new(capacity: u32, per: Duration) -> Self {
+        let capacity = capacity.max(1) as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_rate: capacity / per.as_secs_f64(),
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Забрать токен, если он есть
+    pub fn try_take(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Момент, когда появится следующий токен
+    pub fn next_available(&mut self) -> Instant {
+        self.refill();
+        let missing = (1.0 - self.tokens).max(0.0);
+        self.updated + Duration::from_secs_f64(missing / self.refill_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::advance;
+
+    fn millis_until(at: Instant) -> u128 {
+        at.saturating_duration_since(Instant::now()).as_millis()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_then_one_token_per_interval() {
+        // 5 обновлений за 20 секунд: токен раз в 4 секунды
+        let mut bucket = TokenBucket::new(5, Duration::from_secs(20));
+        for _ in 0..5 {
+            assert!(bucket.try_take());
+        }
+        assert!(!bucket.try_take());
+
+        advance(Duration::from_millis(3_900)).await;
+        assert!(!bucket.try_take());
+
+        advance(Duration::from_millis(110)).await;
+        assert!(bucket.try_take());
+        assert!(!bucket.try_take());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn next_available_points_at_the_next_token() {
+        let mut bucket = TokenBucket::new(5, Duration::from_secs(20));
+        assert_eq!(millis_until(bucket.next_available()), 0);
+
+        for _ in 0..5 {
+            bucket.try_take();
+        }
+        assert_eq!(millis_until(bucket.next_available()), 4_000);
+
+        advance(Duration::from_secs(1)).await;
+        assert_eq!(millis_until(bucket.next_available()), 3_000);
+
+        advance(Duration::from_secs(3)).await;
+        assert!(millis_until(bucket.next_available()) <= 1);
+        assert!(bucket.try_take());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refill_is_capped_at_capacity() {
+        let mut bucket = TokenBucket::new(5, Duration::from_secs(20));
+        bucket.try_take();
+        advance(Duration::from_secs(600)).await;
+
+        let taken = (0..10).filter(|_| bucket.try_take()).count();
+        assert_eq!(taken, 5);
+    }
+}
@@ -0,0 +1,71 @@
+// src/rpc_events.rs
+// События RPC для фронтенда: UI подписывается через listen() вместо опроса get_rpc_status
+use crate::discord_ipc::ReadyData;
+use crate::error::RpcError;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+
+pub const EVENT_CONNECTED: &str = "discord-rpc:connected";
+pub const EVENT_DISCONNECTED: &str = "discord-rpc:disconnected";
+pub const EVENT_READY: &str = "discord-rpc:ready";
+pub const EVENT_ERROR: &str = "discord-rpc:error";
+pub const EVENT_ACTIVITY: &str = "discord-rpc:activity";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum RpcEvent {
+    Connected {
+        app_id: String,
+        endpoint: Option<String>,
+        /// Сколько попыток переподключения понадобилось (0 — первое подключение)
+        reconnect_attempts: u32,
+    },
+    Disconnected {
+        app_id: Option<String>,
+        /// None — соединение закрыто намеренно (close_rpc или смена app_id)
+        error: Option<RpcError>,
+    },
+    /// Пользователь и конфигурация клиента из READY
+    Ready {
+        app_id: String,
+        #[serde(flatten)]
+        ready: ReadyData,
+    },
+    /// Ошибка, о которой иначе узнал бы только лог: evt ERROR от Discord или сбой фоновой отправки
+    Error {
+        app_id: Option<String>,
+        #[serde(flatten)]
+        error: RpcError,
+    },
+    /// Discord подтвердил активность (Null — активность очищена)
+    ActivityAcknowledged {
+        app_id: Option<String>,
+        activity: Value,
+    },
+}
+
+impl RpcEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RpcEvent::Connected { .. } => EVENT_CONNECTED,
+            RpcEvent::Disconnected { .. } => EVENT_DISCONNECTED,
+            RpcEvent::Ready { .. } => EVENT_READY,
+            RpcEvent::Error { .. } => EVENT_ERROR,
+            RpcEvent::ActivityAcknowledged { .. } => EVENT_ACTIVITY,
+        }
+    }
+}
+
+/// Куда воркеры отдают события; в приложении — в Tauri, без него можно просто логировать.
+/// Один sink на весь пул, поэтому события несут app_id.
+pub type EventSink = Arc<dyn Fn(RpcEvent) + Send + Sync>;
+
+pub fn tauri_sink(app: AppHandle) -> EventSink {
+    Arc::new(move |event| {
+        if let Err(e) = app.emit(event.name(), &event) {
+            eprintln!("Failed to emit {}: {}", event.name(), e);
+        }
+    })
+}
@@ -0,0 +1,290 @@
+// src/rpc_pool.rs
+// Пул RPC-воркеров по app_id. У каждого приложения из allowed_processes.json свой app_id,
+// поэтому одновременные активности требуют отдельного IPC-соединения на каждое приложение.
+// Без режима concurrent в пуле живёт один воркер: init_rpc с другим app_id закрывает остальные.
+// Соединения, открытые ради конкретного процесса, закрываются, когда процесс завершается.
+use crate::discord_ipc::{DiscordUser, InstanceSelector};
+use crate::error::RpcError;
+use crate::rpc_events::{EventSink, RpcEvent};
+use crate::rpc_worker::{BackoffConfig, RpcStatus, RpcWorker};
+use crate::windows_api;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+/// Как часто проверять, живы ли процессы, ради которых открыты соединения
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+
+struct PoolEntry {
+    worker: RpcWorker,
+    /// Процесс приложения; None — соединение живёт до close_rpc
+    pid: Option<u32>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, PoolEntry>,
+    /// app_id последнего init_rpc — им пользуются команды, вызванные без app_id
+    current: Option<String>,
+}
+
+pub struct RpcPool {
+    inner: Mutex<Inner>,
+    events: EventSink,
+    /// Пользователь из последнего READY любого воркера; переживает закрытие воркеров
+    user: Arc<Mutex<Option<DiscordUser>>>,
+}
+
+impl RpcPool {
+    pub fn new(events: EventSink) -> Arc<Self> {
+        let user = Arc::new(Mutex::new(None));
+        let captured = user.clone();
+        let events: EventSink = Arc::new(move |event| {
+            if let RpcEvent::Ready { ready, .. } = &event {
+                if let Ok(mut user) = captured.lock() {
+                    *user = Some(ready.user.clone());
+                }
+            }
+            events(event)
+        });
+
+        let pool = Arc::new(Self {
+            inner: Mutex::new(Inner::default()),
+            events,
+            user,
+        });
+        tauri::async_runtime::spawn(reap_loop(Arc::downgrade(&pool)));
+        pool
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // Внутри только карта хэндлов — после паники другого потока ей можно пользоваться
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Подключить `app_id`. Без `concurrent` соединения остальных приложений закрываются.
+    pub async fn connect(
+        &self,
+        app_id: String,
+        instance: InstanceSelector,
+        backoff: BackoffConfig,
+        pid: Option<u32>,
+        concurrent: bool,
+    ) -> Result<(), RpcError> {
+        let (worker, others) = {
+            let mut inner = self.lock();
+            let others: Vec<RpcWorker> = if concurrent {
+                Vec::new()
+            } else {
+                let stale: Vec<String> = inner
+                    .entries
+                    .keys()
+                    .filter(|id| **id != app_id)
+                    .cloned()
+                    .collect();
+                stale
+                    .iter()
+                    .filter_map(|id| inner.entries.remove(id))
+                    .map(|e| e.worker)
+                    .collect()
+            };
+            let entry = inner
+                .entries
+                .entry(app_id.clone())
+                .or_insert_with(|| PoolEntry {
+                    worker: RpcWorker::spawn(self.events.clone()),
+                    pid: None,
+                });
+            if pid.is_some() {
+                entry.pid = pid;
+            }
+            let worker = entry.worker.clone();
+            inner.current = Some(app_id.clone());
+            (worker, others)
+        };
+
+        for other in others {
+            let _ = other.close().await;
+        }
+        worker.connect(app_id, instance, backoff).await
+    }
+
+    /// Воркер для `app_id`, а без него — для последнего init_rpc
+    pub fn worker(&self, app_id: Option<&str>) -> Result<RpcWorker, RpcError> {
+        let inner = self.lock();
+        let id = app_id
+            .map(str::to_string)
+            .or_else(|| inner.current.clone())
+            .ok_or_else(RpcError::not_initialized)?;
+        inner
+            .entries
+            .get(&id)
+            .map(|e| e.worker.clone())
+            .ok_or_else(RpcError::not_initialized)
+    }
+
+    /// Закрыть соединение `app_id`; без app_id — все соединения пула
+    pub async fn close(&self, app_id: Option<&str>) -> Result<(), RpcError> {
+        let workers: Vec<RpcWorker> = {
+            let mut inner = self.lock();
+            match app_id {
+                Some(id) => {
+                    if inner.current.as_deref() == Some(id) {
+                        inner.current = None;
+                    }
+                    inner.entries.remove(id).map(|e| e.worker).into_iter().collect()
+                }
+                None => {
+                    inner.current = None;
+                    inner.entries.drain().map(|(_, e)| e.worker).collect()
+                }
+            }
+        };
+
+        let mut result = Ok(());
+        for worker in workers {
+            if let Err(e) = worker.close().await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Состояние соединения `app_id` (или последнего init_rpc)
+    pub fn status(&self, app_id: Option<&str>) -> RpcStatus {
+        self.worker(app_id)
+            .map(|w| w.status())
+            .unwrap_or_else(|_| RpcStatus::disconnected())
+    }
+
+    /// Состояния всех соединений пула
+    pub fn statuses(&self) -> Vec<RpcStatus> {
+        self.lock().entries.values().map(|e| e.worker.status()).collect()
+    }
+
+    pub fn user(&self) -> Option<DiscordUser> {
+        self.user.lock().ok().and_then(|u| u.clone())
+    }
+
+    /// Закрыть соединения, чьи процессы уже завершились
+    async fn reap(&self) {
+        let dead: Vec<(String, RpcWorker)> = {
+            let mut inner = self.lock();
+            let ids: Vec<String> = inner
+                .entries
+                .iter()
+                .filter(|(_, e)| e.pid.is_some_and(|pid| !windows_api::is_process_alive(pid)))
+                .map(|(id, _)| id.clone())
+                .collect();
+            if ids.iter().any(|id| inner.current.as_ref() == Some(id)) {
+                inner.current = None;
+            }
+            ids.into_iter()
+                .filter_map(|id| inner.entries.remove(&id).map(|e| (id, e.worker)))
+                .collect()
+        };
+
+        for (app_id, worker) in dead {
+            println!("Process for app {} exited, closing its Discord IPC client", app_id);
+            let _ = worker.close().await;
+        }
+    }
+}
+
+async fn reap_loop(pool: Weak<RpcPool>) {
+    loop {
+        tokio::time::sleep(REAP_INTERVAL).await;
+        let Some(pool) = pool.upgrade() else {
+            break;
+        };
+        pool.reap().await;
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::discord_rpc::tests::{wait_for, worker_server, APP_ID};
+    use crate::error::ErrorCode;
+    use crate::rpc_worker::ConnectionState;
+    use serde_json::json;
+
+    const OTHER_APP_ID: &str = "1234567891";
+    const THIRD_APP_ID: &str = "1234567892";
+
+    async fn connect(pool: &RpcPool, app_id: &str, pid: Option<u32>, concurrent: bool) {
+        pool.connect(
+            app_id.to_string(),
+            InstanceSelector::Any,
+            BackoffConfig::default(),
+            pid,
+            concurrent,
+        )
+        .await
+        .unwrap();
+    }
+
+    fn app_ids(pool: &RpcPool) -> Vec<String> {
+        let mut ids: Vec<String> = pool.lock().entries.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn commands_without_app_id_use_last_connected() {
+        let (_env, server) = worker_server("pool-current").await;
+        let pool = RpcPool::new(Arc::new(|_| {}));
+        assert_eq!(pool.worker(None).err().map(|e| e.code), Some(ErrorCode::NotInitialized));
+        assert_eq!(pool.status(None).state, ConnectionState::Disconnected);
+
+        connect(&pool, APP_ID, None, true).await;
+        connect(&pool, OTHER_APP_ID, None, true).await;
+        assert_eq!(pool.status(None).app_id.as_deref(), Some(OTHER_APP_ID));
+        assert_eq!(pool.status(None).state, ConnectionState::Connected);
+
+        let activity = json!({ "details": "Editing scene.c4d" });
+        pool.worker(None).unwrap().set_activity(activity.clone()).await.unwrap();
+        assert_eq!(server.activities(), vec![activity]);
+
+        // Закрытие текущего не переключает команды на другое соединение молча
+        pool.close(Some(OTHER_APP_ID)).await.unwrap();
+        assert_eq!(pool.worker(None).err().map(|e| e.code), Some(ErrorCode::NotInitialized));
+        assert!(pool.worker(Some(APP_ID)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn non_concurrent_connect_closes_other_workers() {
+        let (_env, _server) = worker_server("pool-exclusive").await;
+        let pool = RpcPool::new(Arc::new(|_| {}));
+
+        connect(&pool, APP_ID, None, true).await;
+        connect(&pool, OTHER_APP_ID, None, true).await;
+        assert_eq!(app_ids(&pool), vec![APP_ID, OTHER_APP_ID]);
+        let first = pool.worker(Some(APP_ID)).unwrap();
+
+        connect(&pool, THIRD_APP_ID, None, false).await;
+        assert_eq!(app_ids(&pool), vec![THIRD_APP_ID]);
+        assert_eq!(pool.statuses().len(), 1);
+        wait_for("closed worker", || first.status().state == ConnectionState::Disconnected).await;
+        assert_eq!(pool.status(None).app_id.as_deref(), Some(THIRD_APP_ID));
+    }
+
+    #[tokio::test]
+    async fn reap_drops_connections_of_exited_processes() {
+        let (_env, _server) = worker_server("pool-reap").await;
+        let pool = RpcPool::new(Arc::new(|_| {}));
+
+        // Такого pid не бывает: на Linux pid_max не больше 2^22
+        connect(&pool, APP_ID, Some(u32::MAX), true).await;
+        connect(&pool, OTHER_APP_ID, Some(std::process::id()), true).await;
+        connect(&pool, THIRD_APP_ID, Some(u32::MAX), true).await;
+        let dead = pool.worker(Some(THIRD_APP_ID)).unwrap();
+
+        pool.reap().await;
+        assert_eq!(app_ids(&pool), vec![OTHER_APP_ID]);
+        // Последний init_rpc был для завершившегося процесса
+        assert_eq!(pool.worker(None).err().map(|e| e.code), Some(ErrorCode::NotInitialized));
+        assert_eq!(dead.status().state, ConnectionState::Disconnected);
+        assert_eq!(pool.status(Some(OTHER_APP_ID)).state, ConnectionState::Connected);
+    }
+}
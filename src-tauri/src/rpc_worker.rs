@@ -0,0 +1,530 @@
+// src/rpc_worker.rs
+// Единственный долгоживущий воркер, владеющий IPC-клиентом.
+// Команды приходят по упорядоченному каналу, поэтому более старая активность
+// никогда не перезапишет более новую. Воркер же следит за здоровьем соединения
+// и переподключается с экспоненциальной задержкой, если Discord перезапустился.
+// Обновления проходят через token bucket: пока лимит исчерпан, хранится только
+// самая свежая активность, а неизменившиеся активности не отправляются повторно.
+use crate::discord_ipc::{self, InstanceSelector, IpcClient, IpcError};
+use crate::error::{ErrorCode, RpcError};
+use crate::rpc_events::{EventSink, RpcEvent};
+use crate::rate_limit::TokenBucket;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+const CONNECT_ATTEMPTS: u32 = 6;
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Лимит Discord на SET_ACTIVITY
+const ACTIVITY_BURST: u32 = 5;
+const ACTIVITY_WINDOW: Duration = Duration::from_secs(20);
+
+type Reply = oneshot::Sender<Result<(), RpcError>>;
+
+pub enum RpcCommand {
+    Connect {
+        app_id: String,
+        instance: InstanceSelector,
+        backoff: BackoffConfig,
+        reply: Reply,
+    },
+    SetActivity { activity: Value, reply: Reply },
+    Clear { reply: Reply },
+    Close { reply: Reply },
+}
+
+/// Параметры экспоненциальной задержки между попытками переподключения
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackoffConfig {
+    pub initial_ms: u64,
+    pub max_ms: u64,
+    pub multiplier: f64,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial_ms: 1_000,
+            max_ms: 60_000,
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Задержка перед попыткой номер `attempt` (с 1)
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.saturating_sub(1) as i32);
+        let ms = (self.initial_ms as f64 * factor).min(self.max_ms as f64);
+        Duration::from_millis(ms as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    Backoff,
+}
+
+/// Снимок состояния соединения для get_rpc_status
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcStatus {
+    pub state: ConnectionState,
+    pub app_id: Option<String>,
+    /// Какой экземпляр Discord выбран в init_rpc
+    pub instance: InstanceSelector,
+    pub endpoint: Option<String>,
+    /// Номер попытки переподключения (0 — соединение не терялось)
+    pub reconnect_attempt: u32,
+    /// Задержка до следующей попытки в состоянии Backoff
+    pub retry_delay_ms: Option<u64>,
+    pub last_error: Option<RpcError>,
+}
+
+impl RpcStatus {
+    pub fn disconnected() -> Self {
+        Self {
+            state: ConnectionState::Disconnected,
+            app_id: None,
+            instance: InstanceSelector::Any,
+            endpoint: None,
+            reconnect_attempt: 0,
+            retry_delay_ms: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Хэндл воркера: дёшево клонируется, только отправляет сообщения
+#[derive(Clone)]
+pub struct RpcWorker {
+    tx: mpsc::UnboundedSender<RpcCommand>,
+    status: watch::Receiver<RpcStatus>,
+}
+
+impl RpcWorker {
+    pub fn spawn(events: EventSink) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (status_tx, status) = watch::channel(RpcStatus::disconnected());
+        tauri::async_runtime::spawn(Worker::new(status_tx, events).run(rx));
+        Self { tx, status }
+    }
+
+    pub fn status(&self) -> RpcStatus {
+        self.status.borrow().clone()
+    }
+
+    async fn request(&self, make: impl FnOnce(Reply) -> RpcCommand) -> Result<(), RpcError> {
+        let (reply, rx) = oneshot::channel();
+        self.tx
+            .send(make(reply))
+            .map_err(|_| RpcError::worker_unavailable("RPC worker is not running"))?;
+        rx.await
+            .map_err(|_| RpcError::worker_unavailable("RPC worker dropped the request"))?
+    }
+
+    pub async fn connect(
+        &self,
+        app_id: String,
+        instance: InstanceSelector,
+        backoff: BackoffConfig,
+    ) -> Result<(), RpcError> {
+        self.request(|reply| RpcCommand::Connect {
+            app_id,
+            instance,
+            backoff,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn set_activity(&self, activity: Value) -> Result<(), RpcError> {
+        self.request(|reply| RpcCommand::SetActivity { activity, reply }).await
+    }
+
+    pub async fn clear(&self) -> Result<(), RpcError> {
+        self.request(|reply| RpcCommand::Clear { reply }).await
+    }
+
+    pub async fn close(&self) -> Result<(), RpcError> {
+        self.request(|reply| RpcCommand::Close { reply }).await
+    }
+}
+
+struct Worker {
+    client: Option<IpcClient>,
+    /// app_id, к которому нужно быть подключённым (None — после close_rpc)
+    app_id: Option<String>,
+    /// Экземпляр Discord, к которому подключаемся и переподключаемся
+    instance: InstanceSelector,
+    /// Активность, которую хочет видеть UI (Null — очищена)
+    desired: Value,
+    /// Что сейчас показывает Discord; None — неизвестно (нет соединения)
+    sent: Option<Value>,
+    limiter: TokenBucket,
+    /// Когда лимит позволит отправить отложенную активность
+    flush_at: Option<Instant>,
+    backoff: BackoffConfig,
+    attempt: u32,
+    retry_at: Option<Instant>,
+    status: watch::Sender<RpcStatus>,
+    events: EventSink,
+}
+
+impl Worker {
+    fn new(status: watch::Sender<RpcStatus>, events: EventSink) -> Self {
+        Self {
+            client: None,
+            app_id: None,
+            instance: InstanceSelector::Any,
+            desired: Value::Null,
+            sent: None,
+            limiter: TokenBucket::new(ACTIVITY_BURST, ACTIVITY_WINDOW),
+            flush_at: None,
+            backoff: BackoffConfig::default(),
+            attempt: 0,
+            retry_at: None,
+            status,
+            events,
+        }
+    }
+
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<RpcCommand>) {
+        loop {
+            let connected = self.client.is_some();
+            let retry_at = self.retry_at;
+            let flush_at = self.flush_at;
+            tokio::select! {
+                cmd = rx.recv() => match cmd {
+                    Some(cmd) => self.handle(cmd).await,
+                    None => break,
+                },
+                // Кадр вне команды: PING, CLOSE или обрыв соединения
+                frame = next_frame(&mut self.client), if connected => {
+                    if let Some(c) = self.client.as_mut() {
+                        match c.handle_frame(frame).await {
+                            // ERROR-диспатч без ожидающей команды
+                            Ok(Some(payload)) => {
+                                if let Some(e) = discord_ipc::dispatch_error(&payload) {
+                                    self.emit_error(e.into());
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) if e.is_connection_lost() => self.connection_lost(e.into()),
+                            Err(e) => self.emit_error(e.into()),
+                        }
+                    }
+                }
+                _ = wait_until(retry_at), if retry_at.is_some() => self.reconnect().await,
+                _ = wait_until(flush_at), if flush_at.is_some() => {
+                    if let Err(e) = self.flush().await {
+                        eprintln!("Deferred activity update failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle(&mut self, cmd: RpcCommand) {
+        match cmd {
+            RpcCommand::Connect {
+                app_id,
+                instance,
+                backoff,
+                reply,
+            } => {
+                // Переподключение с другим app_id — старое соединение закрываем
+                if let Some(mut old) = self.client.take() {
+                    let _ = old.close().await;
+                    (self.events)(RpcEvent::Disconnected {
+                        app_id: self.app_id.clone(),
+                        error: None,
+                    });
+                }
+                self.app_id = Some(app_id.clone());
+                self.instance = instance;
+                self.desired = Value::Null;
+                self.backoff = backoff;
+                self.attempt = 0;
+                self.retry_at = None;
+                self.set_state(ConnectionState::Connecting, None);
+
+                let res = match connect_with_retries(&app_id, &self.instance).await {
+                    Ok(client) => {
+                        self.on_connected(client);
+                        Ok(())
+                    }
+                    Err(e) if e.code == ErrorCode::InvalidAppId => {
+                        self.give_up(e.clone());
+                        Err(e)
+                    }
+                    Err(e) => {
+                        // Не сдаёмся: продолжаем пробовать в фоне
+                        self.schedule_retry(e.clone());
+                        Err(e)
+                    }
+                };
+                let _ = reply.send(res);
+            }
+            RpcCommand::SetActivity { activity, reply } => {
+                let _ = reply.send(self.update(activity).await);
+            }
+            RpcCommand::Clear { reply } => {
+                let _ = reply.send(self.update(Value::Null).await);
+            }
+            RpcCommand::Close { reply } => {
+                let res = match self.client.take() {
+                    Some(mut c) => {
+                        let res = c.close().await.map_err(|e| {
+                            RpcError::from(e).context("Failed to close Discord IPC client")
+                        });
+                        (self.events)(RpcEvent::Disconnected {
+                            app_id: self.app_id.clone(),
+                            error: None,
+                        });
+                        res
+                    }
+                    None => Ok(()),
+                };
+                self.app_id = None;
+                self.desired = Value::Null;
+                self.sent = None;
+                self.flush_at = None;
+                self.attempt = 0;
+                self.retry_at = None;
+                self.status.send_replace(RpcStatus::disconnected());
+                println!("Discord RPC client closed.");
+                let _ = reply.send(res);
+            }
+        }
+    }
+
+    /// Запомнить новую желаемую активность и отправить её, если позволяет лимит
+    async fn update(&mut self, activity: Value) -> Result<(), RpcError> {
+        self.desired = activity;
+        match self.client {
+            Some(_) => self.flush().await,
+            // Переподключаемся — активность уйдёт сразу после восстановления
+            None if self.app_id.is_some() => Ok(()),
+            None => Err(RpcError::not_initialized()),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), RpcError> {
+        self.flush_at = None;
+        // Discord уже показывает ровно это — повторно не отправляем
+        if self.sent.as_ref() == Some(&self.desired) {
+            return Ok(());
+        }
+        let Some(c) = self.client.as_mut() else {
+            return Ok(());
+        };
+        if !self.limiter.try_take() {
+            // Лимит исчерпан: более новые обновления просто перезапишут desired
+            self.flush_at = Some(self.limiter.next_available());
+            return Ok(());
+        }
+
+        let activity = self.desired.clone();
+        let (res, action) = if activity.is_null() {
+            (c.clear_activity().await.map(|_| Value::Null), "clear")
+        } else {
+            (c.set_activity(activity.clone()).await, "set")
+        };
+        match res {
+            Ok(acknowledged) => {
+                self.sent = Some(activity);
+                (self.events)(RpcEvent::ActivityAcknowledged {
+                    app_id: self.app_id.clone(),
+                    activity: acknowledged,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                let lost = e.is_connection_lost();
+                let err = RpcError::from(e).context(format_args!("Failed to {} activity", action));
+                self.emit_error(err.clone());
+                if lost {
+                    self.connection_lost(err.clone());
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn on_connected(&mut self, client: IpcClient) {
+        let endpoint = client.endpoint().map(|p| p.display().to_string());
+        (self.events)(RpcEvent::Connected {
+            app_id: self.app_id.clone().unwrap_or_default(),
+            endpoint: endpoint.clone(),
+            reconnect_attempts: self.attempt,
+        });
+        if let Some(ready) = client.ready() {
+            (self.events)(RpcEvent::Ready {
+                app_id: self.app_id.clone().unwrap_or_default(),
+                ready: ready.clone(),
+            });
+        }
+        self.client = Some(client);
+        // У нового соединения активности нет
+        self.sent = Some(Value::Null);
+        self.attempt = 0;
+        self.retry_at = None;
+        self.status.send_modify(|s| {
+            s.state = ConnectionState::Connected;
+            s.endpoint = endpoint;
+            s.reconnect_attempt = 0;
+            s.retry_delay_ms = None;
+            s.last_error = None;
+        });
+    }
+
+    fn connection_lost(&mut self, error: RpcError) {
+        eprintln!("Discord IPC connection lost: {}", error);
+        (self.events)(RpcEvent::Disconnected {
+            app_id: self.app_id.clone(),
+            error: Some(error.clone()),
+        });
+        self.client = None;
+        self.sent = None;
+        self.flush_at = None;
+        self.schedule_retry(error);
+    }
+
+    fn schedule_retry(&mut self, error: RpcError) {
+        self.attempt += 1;
+        let delay = self.backoff.delay(self.attempt);
+        self.retry_at = Some(Instant::now() + delay);
+        let attempt = self.attempt;
+        self.set_state(ConnectionState::Backoff, Some(error));
+        self.status.send_modify(|s| {
+            s.reconnect_attempt = attempt;
+            s.retry_delay_ms = Some(delay.as_millis() as u64);
+        });
+    }
+
+    /// Discord отверг client_id (например, приложение удалено) — переподключение не поможет:
+    /// остаёмся Disconnected с ошибкой, пока UI не вызовет init_rpc заново
+    fn give_up(&mut self, error: RpcError) {
+        self.set_state(ConnectionState::Disconnected, Some(error));
+        self.app_id = None;
+        self.desired = Value::Null;
+        self.sent = None;
+        self.flush_at = None;
+        self.attempt = 0;
+        self.retry_at = None;
+        self.status.send_modify(|s| {
+            s.reconnect_attempt = 0;
+            s.retry_delay_ms = None;
+        });
+    }
+
+    async fn reconnect(&mut self) {
+        self.retry_at = None;
+        let Some(app_id) = self.app_id.clone() else {
+            return;
+        };
+        self.set_state(ConnectionState::Connecting, None);
+
+        let mut client = IpcClient::new(&app_id);
+        if let Err(e) = client.connect(&self.instance).await {
+            let e = RpcError::from(e);
+            if e.code == ErrorCode::InvalidAppId {
+                eprintln!("Discord rejected app id {}, not retrying: {}", app_id, e);
+                self.give_up(e);
+            } else {
+                self.schedule_retry(e);
+            }
+            return;
+        }
+        println!("Discord IPC reconnected after {} attempt(s)", self.attempt);
+        self.on_connected(client);
+
+        // Восстанавливаем последнюю активность
+        if let Err(e) = self.flush().await {
+            eprintln!("Failed to restore activity: {}", e);
+        }
+    }
+
+    fn emit_error(&self, error: RpcError) {
+        (self.events)(RpcEvent::Error {
+            app_id: self.app_id.clone(),
+            error,
+        });
+    }
+
+    fn set_state(&mut self, state: ConnectionState, error: Option<RpcError>) {
+        let app_id = self.app_id.clone();
+        let instance = self.instance.clone();
+        self.status.send_modify(|s| {
+            s.state = state;
+            s.app_id = app_id;
+            s.instance = instance;
+            if state != ConnectionState::Connected {
+                s.endpoint = None;
+            }
+            if error.is_some() {
+                s.last_error = error;
+            }
+        });
+    }
+}
+
+async fn next_frame(client: &mut Option<IpcClient>) -> Result<(u32, Value), IpcError> {
+    match client {
+        Some(c) => c.next_frame().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Ветка select! для необязательного таймера: без срока никогда не срабатывает.
+/// Тем же пользуется presence_engine для опроса и разбора событий окон.
+pub(crate) async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn connect_with_retries(
+    app_id: &str,
+    instance: &InstanceSelector,
+) -> Result<IpcClient, RpcError> {
+    let mut last_err = RpcError::from(IpcError::NotFound);
+
+    for attempt in 1..=CONNECT_ATTEMPTS {
+        let mut client = IpcClient::new(app_id);
+        match client.connect(instance).await {
+            Ok(()) => {
+                println!(
+                    "Discord IPC connected on attempt {}/{} via {:?}",
+                    attempt,
+                    CONNECT_ATTEMPTS,
+                    client.endpoint()
+                );
+                return Ok(client);
+            }
+            Err(e) => {
+                eprintln!(
+                    "Discord connect attempt {}/{} failed: {}",
+                    attempt, CONNECT_ATTEMPTS, e
+                );
+                last_err = RpcError::from(e);
+                if last_err.code == ErrorCode::InvalidAppId {
+                    break;
+                }
+                if attempt < CONNECT_ATTEMPTS {
+                    tokio::time::sleep(CONNECT_RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    Err(last_err.context("Failed to init Discord IPC"))
+}
@@ -0,0 +1,247 @@
+// src/window_focus.rs
+// Какое окно показывать в Discord: учёт фокуса по окнам и политика выбора.
+// Движок отдаёт сюда смены фокуса (события наблюдателя или опрос) и спрашивает,
+// какое окно выбрать, если пользователь не закрепил окно сам.
+use crate::windows_api::WindowInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SelectionPolicy {
+    /// Первое найденное окно, пока оно открыто
+    #[default]
+    First,
+    /// Только окно в фокусе; фокус ушёл в неподдерживаемое приложение — активность очищается,
+    /// а соединение с Discord остаётся открытым, пока окна приложения не закроются
+    Focused,
+    /// Последнее окно в фокусе среди поддерживаемых (переключение в браузер активность не трогает)
+    RecentlyFocused,
+    /// Первое окно по списку процессов; процессы не из списка — после, в порядке обнаружения
+    Priority { processes: Vec<String> },
+}
+
+impl SelectionPolicy {
+    /// Выбрать окно из `windows`; `current` — текущий автоматический выбор
+    pub fn choose(
+        &self,
+        windows: &[WindowInfo],
+        focus: &FocusTracker,
+        current: Option<isize>,
+    ) -> Option<isize> {
+        let listed = |hwnd: isize| windows.iter().any(|w| w.hwnd == hwnd);
+        let focused = focus.focused().filter(|&hwnd| listed(hwnd));
+        let current = current.filter(|&hwnd| listed(hwnd));
+        let first = windows.first().map(|w| w.hwnd);
+
+        match self {
+            SelectionPolicy::First => current.or(first),
+            SelectionPolicy::Focused => focused,
+            SelectionPolicy::RecentlyFocused => focused
+                .or_else(|| {
+                    windows
+                        .iter()
+                        .filter_map(|w| Some((focus.last_focused_ms(w.hwnd)?, w.hwnd)))
+                        .max()
+                        .map(|(_, hwnd)| hwnd)
+                })
+                .or(current)
+                .or(first),
+            SelectionPolicy::Priority { processes } => {
+                let rank = |w: &WindowInfo| {
+                    processes
+                        .iter()
+                        .position(|p| p.eq_ignore_ascii_case(&w.process_name))
+                        .unwrap_or(processes.len())
+                };
+                // min_by_key берёт первый из равных — порядок обнаружения сохраняется
+                windows.iter().min_by_key(|w| rank(w)).map(|w| w.hwnd)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct FocusStats {
+    /// Сколько окно было в фокусе, не считая текущего отрезка
+    total: Duration,
+    /// Когда окно последний раз получило фокус (unix-время в миллисекундах)
+    last_focused_ms: Option<u64>,
+}
+
+/// Окно в фокусе и накопленное время фокуса по окнам
+#[derive(Debug, Default)]
+pub struct FocusTracker {
+    focused: Option<isize>,
+    since: Option<Instant>,
+    stats: HashMap<isize, FocusStats>,
+}
+
+impl FocusTracker {
+    pub fn focused(&self) -> Option<isize> {
+        self.focused
+    }
+
+    /// Запомнить смену фокуса; false — фокус не изменился
+    pub fn set_focused(&mut self, hwnd: Option<isize>, now_ms: u64) -> bool {
+        if hwnd == self.focused {
+            return false;
+        }
+        let now = Instant::now();
+        if let (Some(prev), Some(since)) = (self.focused, self.since) {
+            self.stats.entry(prev).or_default().total += now - since;
+        }
+        self.focused = hwnd;
+        self.since = hwnd.map(|_| now);
+        if let Some(hwnd) = hwnd {
+            self.stats.entry(hwnd).or_default().last_focused_ms = Some(now_ms);
+        }
+        true
+    }
+
+    /// Полное время фокуса, включая текущий отрезок
+    pub fn focus_time(&self, hwnd: isize) -> Duration {
+        let total = self.stats.get(&hwnd).map(|s| s.total).unwrap_or_default();
+        match self.since {
+            Some(since) if self.focused == Some(hwnd) => total + since.elapsed(),
+            _ => total,
+        }
+    }
+
+    pub fn last_focused_ms(&self, hwnd: isize) -> Option<u64> {
+        self.stats.get(&hwnd).and_then(|s| s.last_focused_ms)
+    }
+
+    /// Забыть закрытые окна (hwnd могут переиспользоваться)
+    pub fn retain(&mut self, alive: impl Fn(isize) -> bool) {
+        let focused = self.focused;
+        self.stats
+            .retain(|&hwnd, _| alive(hwnd) || focused == Some(hwnd));
+    }
+
+    /// Заполнить поля фокуса в WindowInfo для UI
+    pub fn annotate(&self, win: &mut WindowInfo) {
+        win.focused = self.focused == Some(win.hwnd);
+        win.focus_time_ms = self.focus_time(win.hwnd).as_millis() as u64;
+        win.last_focused_ms = self.last_focused_ms(win.hwnd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tokio::time::advance;
+
+    /// hwnd 99 — окно браузера: в фокусе бывает, но в списке окон его нет
+    const BROWSER: isize = 99;
+
+    fn window(hwnd: isize, process_name: &str) -> WindowInfo {
+        WindowInfo {
+            hwnd,
+            title: format!("Window {}", hwnd),
+            process_name: process_name.to_string(),
+            pid: None,
+            icon_path: String::new(),
+            display_name: process_name.to_string(),
+            document_name: format!("doc_{}", hwnd),
+            fields: BTreeMap::new(),
+            process_start_time: None,
+            focused: false,
+            focus_time_ms: 0,
+            last_focused_ms: None,
+        }
+    }
+
+    fn apps() -> Vec<WindowInfo> {
+        vec![window(1, "Cinema 4D.exe"), window(2, "AfterFX.exe")]
+    }
+
+    #[test]
+    fn focused_policy_clears_when_unsupported_app_is_focused() {
+        let windows = apps();
+        let mut focus = FocusTracker::default();
+        focus.set_focused(Some(2), 1_000);
+        assert_eq!(SelectionPolicy::Focused.choose(&windows, &focus, Some(1)), Some(2));
+
+        focus.set_focused(Some(BROWSER), 2_000);
+        assert_eq!(SelectionPolicy::Focused.choose(&windows, &focus, Some(2)), None);
+    }
+
+    #[test]
+    fn recently_focused_keeps_last_supported_window() {
+        let windows = apps();
+        let mut focus = FocusTracker::default();
+        focus.set_focused(Some(2), 1_000);
+        focus.set_focused(Some(1), 2_000);
+        focus.set_focused(Some(BROWSER), 3_000);
+        let policy = SelectionPolicy::RecentlyFocused;
+        assert_eq!(policy.choose(&windows, &focus, Some(2)), Some(1));
+
+        // Пока фокуса не было, остаётся текущий выбор
+        let fresh = FocusTracker::default();
+        assert_eq!(policy.choose(&windows, &fresh, Some(2)), Some(2));
+        assert_eq!(policy.choose(&windows, &fresh, None), Some(1));
+    }
+
+    #[test]
+    fn priority_keeps_discovery_order_among_ties() {
+        let windows = vec![
+            window(1, "AfterFX.exe"),
+            window(2, "Cinema 4D.exe"),
+            window(3, "Cinema 4D.exe"),
+            window(4, "Blender.exe"),
+        ];
+        let focus = FocusTracker::default();
+        let priority = |processes: &[&str]| SelectionPolicy::Priority {
+            processes: processes.iter().map(|p| p.to_string()).collect(),
+        };
+        assert_eq!(priority(&["cinema 4d.exe"]).choose(&windows, &focus, Some(3)), Some(2));
+        assert_eq!(
+            priority(&["Blender.exe", "AfterFX.exe"]).choose(&windows, &focus, None),
+            Some(4)
+        );
+        // Ни одного процесса из списка — первое найденное окно
+        assert_eq!(priority(&["Houdini.exe"]).choose(&windows, &focus, Some(3)), Some(1));
+        assert_eq!(priority(&[]).choose(&windows, &focus, None), Some(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn focus_time_accumulates_across_switches() {
+        let mut focus = FocusTracker::default();
+        focus.set_focused(Some(1), 0);
+        advance(Duration::from_secs(3)).await;
+        assert!(focus.set_focused(Some(2), 3_000));
+        assert!(!focus.set_focused(Some(2), 3_000));
+        advance(Duration::from_secs(2)).await;
+        focus.set_focused(Some(1), 5_000);
+        advance(Duration::from_secs(1)).await;
+
+        // Текущий отрезок тоже считается
+        assert_eq!(focus.focus_time(1), Duration::from_secs(4));
+        assert_eq!(focus.focus_time(2), Duration::from_secs(2));
+        assert_eq!(focus.last_focused_ms(1), Some(5_000));
+
+        focus.set_focused(None, 6_000);
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(focus.focus_time(1), Duration::from_secs(4));
+        assert_eq!(focus.focused(), None);
+    }
+
+    #[test]
+    fn retain_keeps_the_focused_window() {
+        let mut focus = FocusTracker::default();
+        focus.set_focused(Some(1), 1_000);
+        focus.set_focused(Some(2), 2_000);
+        focus.set_focused(Some(3), 3_000);
+
+        // Окно 3 ещё не попало в список (его перечитают после события), но оно в фокусе
+        focus.retain(|hwnd| hwnd == 1);
+        assert_eq!(focus.last_focused_ms(1), Some(1_000));
+        assert_eq!(focus.last_focused_ms(2), None);
+        assert_eq!(focus.last_focused_ms(3), Some(3_000));
+        assert_eq!(focus.focused(), Some(3));
+    }
+}
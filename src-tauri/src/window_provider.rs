@@ -0,0 +1,317 @@
+// src/window_provider.rs
+// Откуда берутся окна и процессы. Сопоставление с реестром приложений и разбор заголовка
+// (windows_api) работают поверх WindowProvider и не знают, чей это бэкенд: Win32, X11
+// или сценарий из JSON (фича `fake-windows`), с которым конвейер проверяется без рабочего стола.
+use serde::{Deserialize, Serialize};
+
+#[cfg(windows)]
+use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::{CloseHandle, BOOL, FILETIME, HANDLE, HWND, LPARAM, STILL_ACTIVE},
+    System::ProcessStatus::GetProcessImageFileNameW,
+    System::Threading::{
+        GetExitCodeProcess, GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    },
+    UI::WindowsAndMessaging::{
+        EnumWindows, GetForegroundWindow, GetWindowTextLengthW, GetWindowTextW,
+        GetWindowThreadProcessId, IsWindow, IsWindowVisible,
+    },
+};
+
+#[cfg(not(windows))]
+use crate::x11_api::{self, X11Connection};
+#[cfg(not(windows))]
+use std::sync::Mutex;
+#[cfg(not(windows))]
+use x11rb::errors::ConnectionError;
+
+/// Окно верхнего уровня, как его видит бэкенд: ещё не сопоставлено с приложениями
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawWindow {
+    pub hwnd: isize,
+    pub title: String,
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    /// Имя исполняемого файла, например "AfterFX.exe"
+    pub name: String,
+    /// Время запуска (unix-время в секундах)
+    #[serde(default)]
+    pub start_time: Option<i64>,
+}
+
+pub trait WindowProvider: Send + Sync {
+    /// Видимые окна верхнего уровня с непустым заголовком
+    fn list_windows(&self) -> Vec<RawWindow>;
+
+    /// Одно окно (None — закрыто, скрыто или без заголовка)
+    fn window(&self, hwnd: isize) -> Option<RawWindow>;
+
+    /// None — процесса нет или к нему нет доступа
+    fn process_info(&self, pid: u32) -> Option<ProcessInfo>;
+
+    /// Окно в фокусе (любое, не только поддерживаемое приложение)
+    fn focused_window(&self) -> Option<isize>;
+
+    fn is_window_alive(&self, hwnd: isize) -> bool {
+        self.window(hwnd).is_some()
+    }
+
+    fn is_process_alive(&self, pid: u32) -> bool;
+}
+
+/// Ссылка на бэкенд — тоже бэкенд: так `system()` можно отдать туда, где ждут `Arc<dyn WindowProvider>`
+impl<P: WindowProvider + ?Sized> WindowProvider for &P {
+    fn list_windows(&self) -> Vec<RawWindow> {
+        (**self).list_windows()
+    }
+
+    fn window(&self, hwnd: isize) -> Option<RawWindow> {
+        (**self).window(hwnd)
+    }
+
+    fn process_info(&self, pid: u32) -> Option<ProcessInfo> {
+        (**self).process_info(pid)
+    }
+
+    fn focused_window(&self) -> Option<isize> {
+        (**self).focused_window()
+    }
+
+    fn is_window_alive(&self, hwnd: isize) -> bool {
+        (**self).is_window_alive(hwnd)
+    }
+
+    fn is_process_alive(&self, pid: u32) -> bool {
+        (**self).is_process_alive(pid)
+    }
+}
+
+/// Бэкенд текущей платформы (или сценарий из $JRPCE_WINDOWS_FIXTURE в сборке с `fake-windows`)
+pub fn system() -> &'static dyn WindowProvider {
+    #[cfg(feature = "fake-windows")]
+    if let Some(fake) = crate::fake_windows::PROVIDER.as_ref() {
+        return fake;
+    }
+    #[cfg(windows)]
+    {
+        &Win32Provider
+    }
+    #[cfg(not(windows))]
+    {
+        static X11: X11Provider = X11Provider::new();
+        &X11
+    }
+}
+
+#[cfg(windows)]
+pub struct Win32Provider;
+
+#[cfg(windows)]
+unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let windows = &mut *(lparam.0 as *mut Vec<RawWindow>);
+
+    if let Some(window) = Win32Provider.window(hwnd.0) {
+        windows.push(window);
+    }
+
+    BOOL(1) // Продолжаем перечисление
+}
+
+#[cfg(windows)]
+impl WindowProvider for Win32Provider {
+    fn list_windows(&self) -> Vec<RawWindow> {
+        let mut windows = Vec::new();
+        let _ = unsafe {
+            EnumWindows(
+                Some(enum_windows_proc),
+                LPARAM(&mut windows as *mut _ as isize),
+            )
+        };
+        windows
+    }
+
+    fn window(&self, hwnd: isize) -> Option<RawWindow> {
+        let handle = HWND(hwnd);
+        unsafe {
+            if !IsWindow(handle).as_bool() || !IsWindowVisible(handle).as_bool() {
+                return None;
+            }
+            let length = GetWindowTextLengthW(handle);
+            if length <= 0 {
+                return None;
+            }
+            let mut buffer = vec![0u16; (length + 1) as usize];
+            let read_length = GetWindowTextW(handle, &mut buffer);
+            if read_length <= 0 {
+                return None;
+            }
+            let title = OsString::from_wide(&buffer[..read_length as usize])
+                .to_string_lossy()
+                .into_owned();
+
+            let mut process_id: u32 = 0;
+            GetWindowThreadProcessId(handle, Some(&mut process_id));
+            Some(RawWindow {
+                hwnd,
+                title,
+                pid: Some(process_id),
+            })
+        }
+    }
+
+    // Имя и время запуска процесса по PID
+    fn process_info(&self, pid: u32) -> Option<ProcessInfo> {
+        let handle =
+            unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, BOOL(0), pid) }.ok()?;
+
+        let mut buffer = [0u16; 1024];
+        let length = unsafe { GetProcessImageFileNameW(handle, &mut buffer) };
+        let start_time = process_creation_time(handle);
+        let _ = unsafe { CloseHandle(handle) };
+        let name = if length > 0 {
+            OsString::from_wide(&buffer[..length as usize])
+                .to_string_lossy()
+                .rsplit('\\')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            String::new()
+        };
+        Some(ProcessInfo { name, start_time })
+    }
+
+    fn focused_window(&self) -> Option<isize> {
+        let hwnd = unsafe { GetForegroundWindow() };
+        (hwnd.0 != 0).then_some(hwnd.0)
+    }
+
+    fn is_window_alive(&self, hwnd: isize) -> bool {
+        unsafe {
+            let hwnd = HWND(hwnd);
+            IsWindow(hwnd).as_bool() && IsWindowVisible(hwnd).as_bool()
+        }
+    }
+
+    /// Процесс ещё работает (код выхода STILL_ACTIVE)
+    fn is_process_alive(&self, pid: u32) -> bool {
+        let Ok(handle) = (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, BOOL(0), pid) })
+        else {
+            return false;
+        };
+        let mut exit_code = 0u32;
+        let ok = unsafe { GetExitCodeProcess(handle, &mut exit_code) }.is_ok();
+        let _ = unsafe { CloseHandle(handle) };
+        ok && exit_code == STILL_ACTIVE.0 as u32
+    }
+}
+
+// FILETIME считает 100-нс интервалы с 1601-01-01, переводим в unix-секунды
+#[cfg(windows)]
+fn process_creation_time(handle: HANDLE) -> Option<i64> {
+    const UNIX_EPOCH_AS_FILETIME: i64 = 116_444_736_000_000_000;
+
+    let mut creation = FILETIME::default();
+    let mut exit = FILETIME::default();
+    let mut kernel = FILETIME::default();
+    let mut user = FILETIME::default();
+    unsafe { GetProcessTimes(handle, &mut creation, &mut exit, &mut kernel, &mut user) }.ok()?;
+
+    let ticks = ((creation.dwHighDateTime as i64) << 32) | creation.dwLowDateTime as i64;
+    Some((ticks - UNIX_EPOCH_AS_FILETIME) / 10_000_000)
+}
+
+#[cfg(not(windows))]
+pub struct X11Provider {
+    /// Одно соединение на все запросы; после ошибки соединения открывается заново
+    conn: Mutex<Option<X11Connection>>,
+}
+
+#[cfg(not(windows))]
+impl X11Provider {
+    pub const fn new() -> Self {
+        Self {
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Выполнить запрос на общем соединении. Если соединение умерло (X-сервер перезапущен),
+    /// открываем новое и повторяем один раз; без X-сервера — значение по умолчанию.
+    fn with_connection<T: Default>(
+        &self,
+        query: impl Fn(&X11Connection) -> Result<T, ConnectionError>,
+    ) -> T {
+        // Внутри только соединение — после паники другого потока его можно переоткрыть
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        for _ in 0..2 {
+            if conn.is_none() {
+                *conn = X11Connection::connect();
+            }
+            let Some(x) = conn.as_ref() else {
+                break;
+            };
+            match query(x) {
+                Ok(value) => return value,
+                Err(e) => {
+                    eprintln!("X11 connection lost: {}", e);
+                    *conn = None;
+                }
+            }
+        }
+        T::default()
+    }
+}
+
+#[cfg(not(windows))]
+impl From<x11_api::X11Window> for RawWindow {
+    fn from(w: x11_api::X11Window) -> Self {
+        Self {
+            hwnd: w.id as isize,
+            title: w.title,
+            pid: w.pid,
+        }
+    }
+}
+
+#[cfg(not(windows))]
+impl WindowProvider for X11Provider {
+    fn list_windows(&self) -> Vec<RawWindow> {
+        self.with_connection(X11Connection::list_windows)
+            .into_iter()
+            .map(RawWindow::from)
+            .collect()
+    }
+
+    fn window(&self, hwnd: isize) -> Option<RawWindow> {
+        self.with_connection(|x| x.window(hwnd as u32))
+            .map(RawWindow::from)
+    }
+
+    fn process_info(&self, pid: u32) -> Option<ProcessInfo> {
+        let name = x11_api::process_name(pid);
+        if name.is_empty() {
+            return None;
+        }
+        Some(ProcessInfo {
+            name,
+            start_time: x11_api::process_start_time(pid),
+        })
+    }
+
+    fn focused_window(&self) -> Option<isize> {
+        self.with_connection(X11Connection::focused_window)
+            .map(|id| id as isize)
+    }
+
+    fn is_window_alive(&self, hwnd: isize) -> bool {
+        self.with_connection(|x| x.is_window_alive(hwnd as u32))
+    }
+
+    fn is_process_alive(&self, pid: u32) -> bool {
+        x11_api::is_process_alive(pid)
+    }
+}
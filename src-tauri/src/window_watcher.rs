@@ -0,0 +1,136 @@
+// src/window_watcher.rs
+// Наблюдатель окон: события ОС вместо полного перечисления окон на каждом опросе.
+// X11 — PropertyNotify на _NET_CLIENT_LIST/_NET_ACTIVE_WINDOW/_NET_WM_NAME (x11_api::watch_windows),
+// Windows — SetWinEventHook. Каждый бэкенд живёт в своём потоке и шлёт события в канал;
+// движок активности перечитывает только окна из событий.
+use tokio::sync::mpsc;
+
+/// Что случилось с окном верхнего уровня (hwnd — как в WindowInfo)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowEvent {
+    Created(isize),
+    Destroyed(isize),
+    TitleChanged(isize),
+    /// Окно в фокусе; None — фокус ни у одного окна
+    FocusChanged(Option<isize>),
+}
+
+/// Запустить наблюдатель в отдельном потоке. Канал закроется, если бэкенд не смог
+/// подписаться на события или соединение с оконной системой оборвалось, —
+/// тогда движок возвращается к опросу.
+pub fn spawn() -> mpsc::UnboundedReceiver<WindowEvent> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let spawned = std::thread::Builder::new()
+        .name("window-watcher".to_string())
+        .spawn(move || {
+            if let Err(e) = watch(tx) {
+                eprintln!("Window watcher stopped: {}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        eprintln!("Failed to start window watcher: {}", e);
+    }
+    rx
+}
+
+#[cfg(not(windows))]
+fn watch(tx: mpsc::UnboundedSender<WindowEvent>) -> Result<(), String> {
+    crate::x11_api::watch_windows(|event| tx.send(event).is_ok())
+}
+
+#[cfg(windows)]
+fn watch(tx: mpsc::UnboundedSender<WindowEvent>) -> Result<(), String> {
+    win32::watch(tx)
+}
+
+#[cfg(windows)]
+mod win32 {
+    use super::WindowEvent;
+    use once_cell::sync::OnceCell;
+    use tokio::sync::mpsc;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, GetAncestor, GetMessageW, TranslateMessage, CHILDID_SELF,
+        EVENT_OBJECT_CREATE, EVENT_OBJECT_DESTROY, EVENT_OBJECT_HIDE, EVENT_OBJECT_NAMECHANGE,
+        EVENT_OBJECT_SHOW, EVENT_SYSTEM_FOREGROUND, GA_ROOT, MSG, OBJID_WINDOW,
+        WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+    };
+
+    // У WINEVENTPROC нет пользовательских данных — отправитель лежит в статике
+    static SENDER: OnceCell<mpsc::UnboundedSender<WindowEvent>> = OnceCell::new();
+
+    unsafe extern "system" fn on_win_event(
+        _hook: HWINEVENTHOOK,
+        event: u32,
+        hwnd: HWND,
+        id_object: i32,
+        id_child: i32,
+        _thread: u32,
+        _time: u32,
+    ) {
+        if hwnd.0 == 0 || id_object != OBJID_WINDOW.0 || id_child != CHILDID_SELF as i32 {
+            return;
+        }
+        // Дочерние окна и контролы тоже шлют NAMECHANGE — интересны только окна верхнего уровня
+        let top_level = || GetAncestor(hwnd, GA_ROOT) == hwnd;
+        let event = match event {
+            EVENT_OBJECT_CREATE | EVENT_OBJECT_SHOW if top_level() => WindowEvent::Created(hwnd.0),
+            EVENT_OBJECT_DESTROY | EVENT_OBJECT_HIDE => WindowEvent::Destroyed(hwnd.0),
+            EVENT_OBJECT_NAMECHANGE if top_level() => WindowEvent::TitleChanged(hwnd.0),
+            EVENT_SYSTEM_FOREGROUND => WindowEvent::FocusChanged(Some(hwnd.0)),
+            _ => return,
+        };
+        if let Some(tx) = SENDER.get() {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Хуки вызываются в потоке, который их поставил, поэтому крутим здесь цикл сообщений
+    pub fn watch(tx: mpsc::UnboundedSender<WindowEvent>) -> Result<(), String> {
+        SENDER
+            .set(tx)
+            .map_err(|_| "Window watcher is already running".to_string())?;
+
+        let ranges = [
+            (EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_FOREGROUND),
+            (EVENT_OBJECT_CREATE, EVENT_OBJECT_HIDE),
+            (EVENT_OBJECT_NAMECHANGE, EVENT_OBJECT_NAMECHANGE),
+        ];
+        let hooks: Vec<HWINEVENTHOOK> = ranges
+            .iter()
+            .map(|&(min, max)| unsafe {
+                SetWinEventHook(
+                    min,
+                    max,
+                    None,
+                    Some(on_win_event),
+                    0,
+                    0,
+                    WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+                )
+            })
+            .filter(|hook| !hook.is_invalid())
+            .collect();
+        if hooks.len() != ranges.len() {
+            for hook in hooks {
+                unsafe {
+                    let _ = UnhookWinEvent(hook);
+                }
+            }
+            return Err("SetWinEventHook failed".to_string());
+        }
+
+        let mut msg = MSG::default();
+        unsafe {
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+            for hook in hooks {
+                let _ = UnhookWinEvent(hook);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,175 +1,174 @@
-use std::{ffi::OsString, os::windows::ffi::OsStringExt};
-use windows::{
-    Win32::{
-        Foundation::{BOOL, HWND, LPARAM},
-        System::ProcessStatus::GetProcessImageFileNameW,
-        System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
-        UI::WindowsAndMessaging::{
-            EnumWindows, GetWindowTextLengthW, GetWindowTextW, IsWindowVisible,
-            ShowWindow, SW_MINIMIZE, SW_MAXIMIZE, SW_RESTORE, SW_HIDE,
-            GetWindowPlacement, WINDOWPLACEMENT, IsWindow,
-        },
-    },
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetWindowPlacement, ShowWindow, SW_HIDE, SW_MAXIMIZE, SW_MINIMIZE, SW_RESTORE,
+    WINDOWPLACEMENT,
 };
 
 use regex::Regex;
-use once_cell::sync::Lazy;
-use serde::Deserialize;
+use std::collections::BTreeMap;
 
- 
+use crate::app_registry;
+use crate::error::{ErrorCode, RpcError};
+use crate::window_provider::{self, RawWindow, WindowProvider};
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct WindowInfo {
     pub hwnd: isize,
     pub title: String,
     pub process_name: String,
+    /// PID процесса-владельца окна — по нему пул RPC понимает, что приложение закрылось
+    pub pid: Option<u32>,
     pub icon_path: String,
     pub display_name: String, // Добавляем человекочитаемое имя
     pub document_name: String,
+    /// Именованные группы шаблона заголовка ((?P<version>…), (?P<file>…)) — для шаблонов активности
+    pub fields: BTreeMap<String, String>,
+    /// Время запуска процесса (unix-время в секундах) — для «elapsed» в Discord
+    pub process_start_time: Option<i64>,
+    /// Окно в фокусе (_NET_ACTIVE_WINDOW / GetForegroundWindow)
+    pub focused: bool,
+    /// Сколько окно было в фокусе, пока его видел движок активности
+    pub focus_time_ms: u64,
+    /// Когда окно последний раз получило фокус (unix-время в миллисекундах)
+    pub last_focused_ms: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
-struct AllowedProcessCfg {
-    process_name: String,
-    icon_path: String,
-    display_name: String,
-    #[serde(default)]
-    title_extract_patterns: Vec<String>,
-}
+/// Именованная группа, которая задаёт имя документа вместо группы 1
+const FILE_FIELD: &str = "file";
 
-#[derive(Debug)]
-struct CompiledAllowedProcess {
-    process_name: String,
-    icon_path: String,
-    display_name: String,
-    title_extract_regexes: Vec<Regex>,
+/// Какой шаблон заголовка сработал и что он захватил
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TitleMatch {
+    /// Индекс шаблона в title_extract_patterns
+    pub pattern_index: usize,
+    /// Все группы захвата, [0] — совпадение целиком; None — группа не участвовала
+    pub groups: Vec<Option<String>>,
+    /// Именованные группы, которые участвовали в совпадении
+    pub fields: BTreeMap<String, String>,
+    pub document_name: String,
 }
 
-static ALLOWED_PROCESSES: Lazy<Vec<CompiledAllowedProcess>> = Lazy::new(|| {
-    let json = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/allowed_processes.json"));
-    let cfgs: Vec<AllowedProcessCfg> = serde_json::from_str(json).unwrap_or_default();
-    cfgs
-        .into_iter()
-        .map(|c| CompiledAllowedProcess {
-            process_name: c.process_name,
-            icon_path: c.icon_path,
-            display_name: c.display_name,
-            title_extract_regexes: c
-                .title_extract_patterns
-                .into_iter()
-                .filter_map(|p| Regex::new(&p).ok())
+/// Первый сработавший шаблон. Имя документа — группа `file`, без неё группа 1,
+/// а без групп всё совпадение
+pub fn match_title(regexes: &[Regex], window_title: &str) -> Option<TitleMatch> {
+    regexes.iter().enumerate().find_map(|(pattern_index, re)| {
+        let caps = re.captures(window_title)?;
+        let fields: BTreeMap<String, String> = re
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_string(), caps.name(name)?.as_str().to_string())))
+            .collect();
+        let document_name = match fields.get(FILE_FIELD) {
+            Some(file) => file.clone(),
+            None => caps.get(1).or_else(|| caps.get(0))?.as_str().to_string(),
+        };
+        Some(TitleMatch {
+            pattern_index,
+            groups: caps
+                .iter()
+                .map(|m| m.map(|m| m.as_str().to_string()))
                 .collect(),
+            fields,
+            document_name,
         })
-        .collect()
-});
-
-fn extract_document_name(cfg: &CompiledAllowedProcess, window_title: &str) -> String {
-    for re in &cfg.title_extract_regexes {
-        if let Some(caps) = re.captures(window_title) {
-            if let Some(m) = caps.get(1) {
-                return m.as_str().to_string();
-            }
-            if let Some(m) = caps.get(0) {
-                return m.as_str().to_string();
-            }
-        }
-    }
-    String::new()
-}
-unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
-    let windows = &mut *(lparam.0 as *mut Vec<WindowInfo>);
-    // Список: (имя_процесса, путь_к_иконке, display_name)
-
-    if IsWindowVisible(hwnd).as_bool() {
-        let length = GetWindowTextLengthW(hwnd);
-        if length > 0 {
-            let mut buffer = vec![0u16; (length + 1) as usize];
-            let read_length = GetWindowTextW(hwnd, &mut buffer);
-            if read_length > 0 {
-                let title = OsString::from_wide(&buffer[..read_length as usize])
-                    .to_string_lossy()
-                    .into_owned();
-
-                // Получаем имя процесса
-                let process_name = get_process_name(hwnd);
-                if let Some(cfg) = ALLOWED_PROCESSES
-                    .iter()
-                    .find(|cfg| cfg.process_name == process_name)
-                {
-                    let document_name = extract_document_name(cfg, &title);
-                    windows.push(WindowInfo {
-                        hwnd: hwnd.0,
-                        title,
-                        process_name,
-                        icon_path: cfg.icon_path.clone(),
-                        display_name: cfg.display_name.clone(),
-                        document_name,
-                    });
-                }
-            }
-        }
-    }
+    })
+}
 
-    BOOL(1) // Продолжаем перечисление
+/// Общий для всех бэкендов шаг: сверяем процесс с реестром приложений и достаём имя документа
+fn match_window(
+    hwnd: isize,
+    title: String,
+    process_name: String,
+    pid: Option<u32>,
+    process_start_time: Option<i64>,
+) -> Option<WindowInfo> {
+    let registry = app_registry::current();
+    let cfg = registry.find(&process_name)?;
+    let (document_name, fields) = match_title(&cfg.title_extract_regexes, &title)
+        .map(|m| (m.document_name, m.fields))
+        .unwrap_or_default();
+    Some(WindowInfo {
+        hwnd,
+        title,
+        process_name,
+        pid,
+        icon_path: cfg.config.icon_path.clone(),
+        display_name: cfg.config.display_name.clone(),
+        document_name,
+        fields,
+        process_start_time,
+        focused: false,
+        focus_time_ms: 0,
+        last_focused_ms: None,
+    })
 }
 
-// Функция для получения имени процесса по HWND
-fn get_process_name(hwnd: HWND) -> String {
-    let mut process_id: u32 = 0;
-    unsafe {
-        windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId(hwnd, Some(&mut process_id));
-    }
+/// Окно поддерживаемого приложения: процесс окна сверяется с реестром приложений,
+/// из заголовка достаётся имя документа
+pub fn match_raw_window(
+    provider: &dyn WindowProvider,
+    raw: RawWindow,
+    focused: Option<isize>,
+) -> Option<WindowInfo> {
+    let process = raw
+        .pid
+        .and_then(|pid| provider.process_info(pid))
+        .unwrap_or_default();
+    let is_focused = focused == Some(raw.hwnd);
+    let mut info = match_window(raw.hwnd, raw.title, process.name, raw.pid, process.start_time)?;
+    info.focused = is_focused;
+    Some(info)
+}
 
-    let process_handle = unsafe {
-        OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, BOOL(0), process_id)
-    };
+/// Окна поддерживаемых приложений в порядке перечисления
+pub fn detect_windows(provider: &dyn WindowProvider) -> Vec<WindowInfo> {
+    let focused = provider.focused_window();
+    provider
+        .list_windows()
+        .into_iter()
+        .filter_map(|raw| match_raw_window(provider, raw, focused))
+        .collect()
+}
 
-    let process_name = if let Ok(handle) = process_handle {
-        let mut buffer = [0u16; 1024];
-        let length = unsafe {
-            GetProcessImageFileNameW(handle, &mut buffer)
-        };
-        let _ = unsafe { windows::Win32::Foundation::CloseHandle(handle) };
-        if length > 0 {
-            OsString::from_wide(&buffer[..length as usize])
-                .to_string_lossy()
-                .into_owned()
-                .split('\\')
-                .last()
-                .unwrap_or_default()
-                .to_string()
-        } else {
-            String::new()
-        }
-    } else {
-        String::new()
-    };
+/// Одно окно, если оно принадлежит поддерживаемому приложению.
+/// Наблюдатель окон вызывает это для окна из события вместо полного перечисления;
+/// окно в фокусе он уже знает из событий, поэтому `focused` передаёт сам.
+pub fn inspect_window(
+    provider: &dyn WindowProvider,
+    hwnd: isize,
+    focused: Option<isize>,
+) -> Option<WindowInfo> {
+    provider
+        .window(hwnd)
+        .and_then(|raw| match_raw_window(provider, raw, focused))
+}
 
-    process_name
+pub fn is_process_alive(pid: u32) -> bool {
+    window_provider::system().is_process_alive(pid)
 }
 
 #[tauri::command]
 pub fn get_windows_list() -> Vec<WindowInfo> {
-    let mut windows = Vec::new();
-    let _ = unsafe { EnumWindows(Some(enum_windows_proc), LPARAM(&mut windows as *mut _ as isize)) };
-    windows
+    detect_windows(window_provider::system())
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub fn minimize_window() -> Result<(), String> {
+pub fn minimize_window() -> Result<(), RpcError> {
     unsafe {
         let hwnd = windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow();
         if hwnd.0 != 0 {
             ShowWindow(hwnd, SW_MINIMIZE);
             Ok(())
         } else {
-            Err("Failed to get foreground window".to_string())
+            Err(RpcError::new(ErrorCode::WindowNotFound, "Failed to get foreground window"))
         }
     }
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub fn toggle_maximize_window() -> Result<(), String> {
+pub fn toggle_maximize_window() -> Result<(), RpcError> {
     unsafe {
         let hwnd = windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow();
         if hwnd.0 != 0 {
@@ -184,37 +183,155 @@ pub fn toggle_maximize_window() -> Result<(), String> {
                 }
                 Ok(())
             } else {
-                Err("Failed to get window placement".to_string())
+                Err(RpcError::new(ErrorCode::WindowOperationFailed, "Failed to get window placement"))
             }
         } else {
-            Err("Failed to get foreground window".to_string())
+            Err(RpcError::new(ErrorCode::WindowNotFound, "Failed to get foreground window"))
         }
     }
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub fn close_window() -> Result<(), String> {
+pub fn close_window() -> Result<(), RpcError> {
     unsafe {
         let hwnd = windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow();
         if hwnd.0 != 0 {
             ShowWindow(hwnd, SW_HIDE);
             Ok(())
         } else {
-            Err("Failed to get foreground window".to_string())
+            Err(RpcError::new(ErrorCode::WindowNotFound, "Failed to get foreground window"))
         }
     }
 }
 
+// Вне Windows нет «foreground window» в смысле Win32 — управляем собственным окном через Tauri
+#[cfg(not(windows))]
+fn window_error(action: &str, e: tauri::Error) -> RpcError {
+    RpcError::new(ErrorCode::WindowOperationFailed, format!("{}: {}", action, e))
+}
+
+#[cfg(not(windows))]
 #[tauri::command]
-pub fn is_window_active(hwnd: isize) -> bool {
-    unsafe {
-        let hwnd = HWND(hwnd);
-        IsWindow(hwnd).as_bool() && IsWindowVisible(hwnd).as_bool()
+pub fn minimize_window(window: tauri::Window) -> Result<(), RpcError> {
+    window.minimize().map_err(|e| window_error("Failed to minimize window", e))
+}
+
+#[cfg(not(windows))]
+#[tauri::command]
+pub fn toggle_maximize_window(window: tauri::Window) -> Result<(), RpcError> {
+    let maximized = window
+        .is_maximized()
+        .map_err(|e| window_error("Failed to get window state", e))?;
+    if maximized {
+        window.unmaximize()
+    } else {
+        window.maximize()
     }
+    .map_err(|e| window_error("Failed to toggle maximize", e))
+}
+
+#[cfg(not(windows))]
+#[tauri::command]
+pub fn close_window(window: tauri::Window) -> Result<(), RpcError> {
+    window.hide().map_err(|e| window_error("Failed to hide window", e))
+}
+
+#[tauri::command]
+pub fn is_window_active(hwnd: isize) -> bool {
+    window_provider::system().is_window_alive(hwnd)
 }
 
 #[tauri::command]
 pub fn get_app_version() -> String {
     // Читаем версию из tauri.conf.json во время компиляции
     env!("CARGO_PKG_VERSION").to_string()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_windows::{FakeWindowProvider, Fixture};
+
+    fn fixture_provider() -> FakeWindowProvider {
+        let json = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/windows.json"));
+        FakeWindowProvider::new(Fixture::from_json(json).unwrap())
+    }
+
+    #[test]
+    fn detects_only_registered_apps_with_titles() {
+        let windows = detect_windows(&fixture_provider());
+        // 1003 — notepad.exe, которого нет в реестре; 1004 — окно Cinema 4D без заголовка
+        let hwnds: Vec<isize> = windows.iter().map(|w| w.hwnd).collect();
+        assert_eq!(hwnds, vec![1001, 1002]);
+
+        let c4d = &windows[0];
+        assert_eq!(c4d.process_name, "Cinema 4D.exe");
+        assert_eq!(c4d.display_name, "Cinema 4D");
+        assert_eq!(c4d.pid, Some(4100));
+
+        let ae = &windows[1];
+        assert_eq!(ae.process_name, "AfterFX.exe");
+        assert_eq!(ae.display_name, "Adobe After Effects");
+        assert_eq!(ae.pid, Some(4200));
+    }
+
+    #[test]
+    fn document_name_comes_from_title_pattern() {
+        let windows = detect_windows(&fixture_provider());
+        assert_eq!(windows[0].document_name, "scene_final.c4d *");
+        assert_eq!(windows[1].document_name, "/Projects/intro.aep");
+    }
+
+    #[test]
+    fn named_groups_become_fields_and_file_is_the_document() {
+        let regexes = [
+            Regex::new(r" - (.+\.aep)$").unwrap(),
+            Regex::new(
+                r"^Cinema 4D (?P<version>[\d.]+) - \[(?P<file>[^\]*]+?)(?: (?P<dirty>\*))?\] - (?P<layout>.+)$",
+            )
+            .unwrap(),
+        ];
+
+        let dirty = match_title(&regexes, "Cinema 4D 2024.1 - [scene_v12.c4d *] - Main").unwrap();
+        assert_eq!(dirty.pattern_index, 1);
+        // Группа 1 — версия, но имя документа берётся из `file`
+        assert_eq!(dirty.groups[1].as_deref(), Some("2024.1"));
+        assert_eq!(dirty.document_name, "scene_v12.c4d");
+        assert_eq!(dirty.fields.get("dirty").map(String::as_str), Some("*"));
+
+        let saved = match_title(&regexes, "Cinema 4D 2024.1 - [scene_v12.c4d] - Main").unwrap();
+        assert_eq!(saved.document_name, "scene_v12.c4d");
+        assert_eq!(saved.groups[3], None);
+        let fields: Vec<(&str, &str)> = saved
+            .fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![("file", "scene_v12.c4d"), ("layout", "Main"), ("version", "2024.1")]
+        );
+    }
+
+    #[test]
+    fn process_start_time_and_focus() {
+        let windows = detect_windows(&fixture_provider());
+        assert_eq!(windows[0].process_start_time, Some(1_760_000_000));
+        assert_eq!(windows[1].process_start_time, Some(1_760_003_600));
+        assert!(!windows[0].focused);
+        assert!(windows[1].focused);
+    }
+
+    #[test]
+    fn single_window_skips_unlisted_and_untitled() {
+        let provider = fixture_provider();
+        let inspect = |hwnd| inspect_window(&provider, hwnd, Some(1001));
+        let c4d = inspect(1001).unwrap();
+        assert_eq!(c4d.document_name, "scene_final.c4d *");
+        assert!(c4d.focused);
+        assert!(inspect(1003).is_none());
+        assert!(inspect(1004).is_none());
+        assert!(inspect(9999).is_none());
+    }
+}
@@ -0,0 +1,369 @@
+// src/x11_api.rs
+// X11-бэкенд перечисления окон для Linux (EWMH: _NET_CLIENT_LIST, _NET_WM_NAME, _NET_WM_PID)
+use crate::window_watcher::WindowEvent;
+use std::collections::HashSet;
+use std::fs;
+use x11rb::atom_manager;
+use x11rb::connection::Connection;
+use x11rb::errors::{ConnectionError, ReplyError};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, GetPropertyReply, Window,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+atom_manager! {
+    Atoms: AtomsCookie {
+        _NET_CLIENT_LIST,
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        UTF8_STRING,
+    }
+}
+
+/// Окно верхнего уровня, как его видит оконный менеджер
+#[derive(Debug)]
+pub struct X11Window {
+    pub id: Window,
+    pub title: String,
+    pub pid: Option<u32>,
+}
+
+/// Соединение с X-сервером из $DISPLAY + корневое окно и нужные атомы.
+/// Ошибки запросов к исчезнувшему окну не считаются ошибкой: ConnectionError значит,
+/// что соединение мертво и его нужно открыть заново.
+pub struct X11Connection {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+}
+
+impl X11Connection {
+    pub fn connect() -> Option<Self> {
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots.get(screen_num)?.root;
+        let atoms = Atoms::new(&conn).ok()?.reply().ok()?;
+        Some(Self { conn, root, atoms })
+    }
+
+    /// None — окна уже нет (BadWindow и т.п.)
+    fn property(
+        &self,
+        window: Window,
+        property: impl Into<Atom>,
+        type_: impl Into<Atom>,
+        long_length: u32,
+    ) -> Result<Option<GetPropertyReply>, ConnectionError> {
+        let cookie = self
+            .conn
+            .get_property(false, window, property, type_, 0, long_length)?;
+        match cookie.reply() {
+            Ok(reply) => Ok(Some(reply)),
+            Err(ReplyError::X11Error(_)) => Ok(None),
+            Err(ReplyError::ConnectionError(e)) => Err(e),
+        }
+    }
+
+    fn client_list(&self) -> Result<Vec<Window>, ConnectionError> {
+        let reply = self.property(
+            self.root,
+            self.atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            u32::MAX,
+        )?;
+        Ok(reply
+            .and_then(|reply| reply.value32().map(|ids| ids.collect()))
+            .unwrap_or_default())
+    }
+
+    fn window_title(&self, window: Window) -> Result<String, ConnectionError> {
+        // Сначала _NET_WM_NAME (UTF-8), затем устаревший WM_NAME
+        let utf8 = self.property(
+            window,
+            self.atoms._NET_WM_NAME,
+            self.atoms.UTF8_STRING,
+            u32::MAX,
+        )?;
+        if let Some(reply) = utf8 {
+            if !reply.value.is_empty() {
+                return Ok(String::from_utf8_lossy(&reply.value).into_owned());
+            }
+        }
+
+        Ok(self
+            .property(window, AtomEnum::WM_NAME, AtomEnum::ANY, u32::MAX)?
+            .map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+            .unwrap_or_default())
+    }
+
+    fn window_pid(&self, window: Window) -> Result<Option<u32>, ConnectionError> {
+        Ok(self
+            .property(window, self.atoms._NET_WM_PID, AtomEnum::CARDINAL, 1)?
+            .and_then(|reply| reply.value32()?.next()))
+    }
+
+    /// Окно в фокусе по _NET_ACTIVE_WINDOW
+    pub fn focused_window(&self) -> Result<Option<Window>, ConnectionError> {
+        Ok(self
+            .property(
+                self.root,
+                self.atoms._NET_ACTIVE_WINDOW,
+                AtomEnum::WINDOW,
+                1,
+            )?
+            .and_then(|reply| reply.value32()?.next())
+            .filter(|&id| id != 0))
+    }
+
+    /// Все окна из _NET_CLIENT_LIST с непустым заголовком
+    pub fn list_windows(&self) -> Result<Vec<X11Window>, ConnectionError> {
+        let mut windows = Vec::new();
+        for id in self.client_list()? {
+            let title = self.window_title(id)?;
+            if !title.is_empty() {
+                windows.push(X11Window {
+                    id,
+                    pid: self.window_pid(id)?,
+                    title,
+                });
+            }
+        }
+        Ok(windows)
+    }
+
+    /// Одно окно из _NET_CLIENT_LIST (None — закрыто или без заголовка)
+    pub fn window(&self, id: Window) -> Result<Option<X11Window>, ConnectionError> {
+        if !self.client_list()?.contains(&id) {
+            return Ok(None);
+        }
+        let title = self.window_title(id)?;
+        if title.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(X11Window {
+            id,
+            pid: self.window_pid(id)?,
+            title,
+        }))
+    }
+
+    /// Окно живо, пока оконный менеджер держит его в _NET_CLIENT_LIST
+    pub fn is_window_alive(&self, id: Window) -> Result<bool, ConnectionError> {
+        Ok(self.client_list()?.contains(&id))
+    }
+}
+
+/// Следить за окнами через PropertyNotify: список клиентов и активное окно — на корневом окне,
+/// заголовки — на каждом клиенте. Блокирует поток, пока `send` возвращает true.
+/// Соединение своё: wait_for_event не должен мешать запросам X11Provider.
+pub fn watch_windows(mut send: impl FnMut(WindowEvent) -> bool) -> Result<(), String> {
+    let x = X11Connection::connect().ok_or("Failed to connect to X server")?;
+    let (conn, root, atoms) = (&x.conn, x.root, &x.atoms);
+    let property_changes = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+    let subscribe = |window: Window| {
+        // Окно может исчезнуть раньше, чем мы подпишемся, — это не ошибка
+        let _ = conn.change_window_attributes(window, &property_changes);
+    };
+
+    subscribe(root);
+    let mut known: HashSet<Window> = x
+        .client_list()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+    known.iter().copied().for_each(subscribe);
+    conn.flush().map_err(|e| e.to_string())?;
+
+    loop {
+        let Event::PropertyNotify(e) = conn.wait_for_event().map_err(|e| e.to_string())? else {
+            continue;
+        };
+        let events = if e.window == root && e.atom == atoms._NET_CLIENT_LIST {
+            let current: HashSet<Window> = x
+                .client_list()
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .collect();
+            let mut events = Vec::new();
+            for &id in current.difference(&known) {
+                subscribe(id);
+                events.push(WindowEvent::Created(id as isize));
+            }
+            for &id in known.difference(&current) {
+                events.push(WindowEvent::Destroyed(id as isize));
+            }
+            known = current;
+            conn.flush().map_err(|e| e.to_string())?;
+            events
+        } else if e.window == root && e.atom == atoms._NET_ACTIVE_WINDOW {
+            let active = x.focused_window().map_err(|e| e.to_string())?;
+            vec![WindowEvent::FocusChanged(active.map(|id| id as isize))]
+        } else if e.atom == atoms._NET_WM_NAME || e.atom == u32::from(AtomEnum::WM_NAME) {
+            vec![WindowEvent::TitleChanged(e.window as isize)]
+        } else {
+            continue;
+        };
+
+        for event in events {
+            if !send(event) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Имя исполняемого файла процесса через /proc/<pid>/exe.
+/// Windows-программы под Wine/Proton видны как wine64-preloader и т.п. — для них берём
+/// имя .exe из командной строки, чтобы записи allowed_processes.json совпадали как на Windows.
+pub fn process_name(pid: u32) -> String {
+    let exe = fs::read_link(format!("/proc/{}/exe", pid))
+        .ok()
+        .and_then(|path| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+    let environ = proc_strings(pid, "environ");
+    let cmdline = proc_strings(pid, "cmdline");
+    wine_program(&exe, &environ, &cmdline).unwrap_or(exe)
+}
+
+/// Загрузчики Wine и Proton, под которыми запускаются Windows-программы
+const WINE_LOADERS: [&str; 4] = ["wine", "wine64", "wine-preloader", "wine64-preloader"];
+
+/// Переменные окружения, которые Wine и Proton передают запущенным программам
+const WINE_ENV_MARKERS: [&str; 4] = [
+    "WINEPREFIX=",
+    "WINELOADER=",
+    "WINESERVERSOCKET=",
+    "STEAM_COMPAT_DATA_PATH=",
+];
+
+/// Аргументы процесса из /proc/<pid>/<file> (cmdline и environ разделены NUL)
+fn proc_strings(pid: u32, file: &str) -> Vec<String> {
+    fs::read(format!("/proc/{}/{}", pid, file))
+        .map(|bytes| {
+            bytes
+                .split(|&b| b == 0)
+                .filter(|s| !s.is_empty())
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Имя Windows-программы, если процесс — загрузчик Wine (по имени exe) или
+/// переименованная копия из Proton (по окружению); для обычных процессов None
+fn wine_program(exe: &str, environ: &[String], cmdline: &[String]) -> Option<String> {
+    if !WINE_LOADERS.contains(&exe) && !has_wine_env(environ) {
+        return None;
+    }
+    wine_exe_from_cmdline(cmdline)
+}
+
+fn has_wine_env(environ: &[String]) -> bool {
+    environ
+        .iter()
+        .any(|var| WINE_ENV_MARKERS.iter().any(|m| var.starts_with(m)))
+}
+
+/// Имя .exe из командной строки: Wine подменяет argv[0] на путь к .exe
+/// ("C:\...\AfterFX.exe"), а при запуске через `wine64 app.exe` путь идёт аргументом
+fn wine_exe_from_cmdline(cmdline: &[String]) -> Option<String> {
+    cmdline
+        .iter()
+        .map(|arg| arg.rsplit(['\\', '/']).next().unwrap_or(arg))
+        .find(|name| name.len() > 4 && name.as_bytes()[name.len() - 4..].eq_ignore_ascii_case(b".exe"))
+        .map(str::to_string)
+}
+
+/// Процесс жив, пока есть /proc/<pid> и он не зомби
+pub fn is_process_alive(pid: u32) -> bool {
+    match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        // Состояние — первое поле после comm
+        Ok(stat) => stat
+            .rfind(')')
+            .and_then(|i| stat[i + 1..].split_whitespace().next())
+            .is_some_and(|state| state != "Z" && state != "X"),
+        Err(_) => false,
+    }
+}
+
+/// Время запуска процесса (unix-секунды): starttime из /proc/<pid>/stat + btime из /proc/stat
+pub fn process_start_time(pid: u32) -> Option<i64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // comm может содержать пробелы и скобки — поля считаем после последней ')'
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    // starttime — 22-е поле, после comm идёт 3-е
+    let start_ticks: i64 = after_comm.split_whitespace().nth(19)?.parse().ok()?;
+
+    let boot_time: i64 = fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+    Some(boot_time + start_ticks / ticks_per_sec as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn exe_from_wine_argv0() {
+        let cmdline = strings(&[
+            r"C:\Program Files\Adobe\Adobe After Effects 2024\Support Files\AfterFX.exe",
+            "-noui",
+        ]);
+        assert_eq!(wine_exe_from_cmdline(&cmdline).as_deref(), Some("AfterFX.exe"));
+    }
+
+    #[test]
+    fn exe_from_loader_argument() {
+        let cmdline = strings(&["wine64", "/home/u/c4d/Cinema 4D.exe"]);
+        assert_eq!(wine_exe_from_cmdline(&cmdline).as_deref(), Some("Cinema 4D.exe"));
+        assert_eq!(
+            wine_program("wine64-preloader", &[], &cmdline).as_deref(),
+            Some("Cinema 4D.exe")
+        );
+    }
+
+    #[test]
+    fn proton_copy_is_detected_by_environment() {
+        let environ = strings(&[
+            "HOME=/home/u",
+            "STEAM_COMPAT_DATA_PATH=/home/u/.steam/steam/steamapps/compatdata/1234",
+        ]);
+        let cmdline = strings(&[r"Z:\home\u\Games\Cinema 4D\Cinema 4D.exe"]);
+        assert!(has_wine_env(&environ));
+        assert_eq!(
+            wine_program("Cinema 4D.exe", &environ, &cmdline).as_deref(),
+            Some("Cinema 4D.exe")
+        );
+    }
+
+    #[test]
+    fn native_process_keeps_its_name() {
+        let environ = strings(&["HOME=/home/u", "PATH=/usr/bin"]);
+        let cmdline = strings(&["7z", "x", "/home/u/Downloads/setup.exe"]);
+        assert!(!has_wine_env(&environ));
+        assert_eq!(wine_program("7z", &environ, &cmdline), None);
+    }
+
+    #[test]
+    fn exe_suffix_is_case_insensitive() {
+        let cmdline = strings(&[r"C:\PROGRAM FILES\MAXON\CINEMA 4D.EXE"]);
+        assert_eq!(wine_exe_from_cmdline(&cmdline).as_deref(), Some("CINEMA 4D.EXE"));
+        // Просто «.exe» без имени — не программа
+        assert_eq!(wine_exe_from_cmdline(&strings(&["wine", ".exe"])), None);
+    }
+}
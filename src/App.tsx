@@ -4,23 +4,28 @@ import { useState, useEffect, useRef, useCallback} from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { Profile } from './components/Profile';
 import './App.css';
-import { loadConfig, saveConfig, Config, Role } from "./Config";
-
-interface WindowInfo {
-  hwnd: string;
-  title: string;
-  process_name: string;
-  icon_path: string;
-  display_name: string;
-  document_name: string;
+import { loadConfig, saveConfig, Config, DiscordInstance, Role, SelectionPolicy } from "./Config";
+import { formatRpcError } from "./rpcError";
+import { DiscordUser, discordAvatarUrl, subscribeRpcEvents } from "./rpcEvents";
+import { EngineState, WindowInfo, subscribeEngineState } from "./presenceEngine";
+import { iconSrc } from "./appRegistry";
+
+// <select> хранит строку: "any", "build:canary", "index:1"
+function instanceToValue(instance: DiscordInstance): string {
+  switch (instance.kind) {
+    case "build": return `build:${instance.build}`;
+    case "index": return `index:${instance.index}`;
+    default: return "any";
+  }
 }
 
-interface AppConfigItem {
-  process_name: string;
-  icon_path: string;
-  display_name: string;
-  app_id?: string;
-  title_extract_patterns?: string[];
+function valueToInstance(value: string): DiscordInstance {
+  const [kind, arg] = value.split(":");
+  if (kind === "build" && (arg === "stable" || arg === "ptb" || arg === "canary")) {
+    return { kind: "build", build: arg };
+  }
+  if (kind === "index") return { kind: "index", index: Number(arg) };
+  return { kind: "any" };
 }
 
 function App() {
@@ -33,12 +38,18 @@ function App() {
   const [autoCheckInterval, setAutoCheckInterval] = useState<number>(5000);
   const [lastCheckTime, setLastCheckTime] = useState<Date>(new Date());
   const [isAutoChecking, setIsAutoChecking] = useState<boolean>(true);
-
-  const [appsConfig, setAppsConfig] = useState<AppConfigItem[]>([]);
+  const [discordInstance, setDiscordInstance] = useState<DiscordInstance>({ kind: "any" });
+  // Своя активность у каждого найденного приложения (отдельное соединение на app_id)
+  const [concurrentPresences, setConcurrentPresences] = useState<boolean>(false);
+  const [selectionPolicy, setSelectionPolicy] = useState<SelectionPolicy>({ kind: "first" });
+  const [paused, setPaused] = useState<boolean>(false);
 
   // Profile state
   const [displayName, setDisplayName] = useState<string>('Your Name');
   const [handleName, setHandleName] = useState<string>('@username');
+  const [syncFromDiscord, setSyncFromDiscord] = useState<boolean>(false);
+  const [avatarUrl, setAvatarUrl] = useState<string | undefined>(undefined);
+  const [discordUser, setDiscordUser] = useState<DiscordUser | null>(null);
   const [roles, setRoles] = useState<Role[]>([
     { id: '1', name: 'Developer', color: '#5865f2' },
     { id: '2', name: 'Designer', color: '#eb459e' }
@@ -52,8 +63,8 @@ function App() {
   const [activityTime, setActivityTime] = useState<number>(0);
   const activityWrapRef = useRef<HTMLDivElement>(null);
 
-  const autoCheckTimerRef = useRef<ReturnType<typeof setInterval> | null>(null);
-  const currentAppIdRef = useRef<string | null>(null);
+  // hwnd окна активности из последнего состояния движка — чтобы анимировать только смену окна
+  const selectedHwndRef = useRef<number | null>(null);
   const saveTimeoutRef = useRef<ReturnType<typeof setTimeout> | null>(null);
 
   // Activity timer
@@ -63,6 +74,15 @@ function App() {
       return;
     }
 
+    // Считаем от реального запуска процесса, если бэкенд его знает
+    const startedAt = activityWindow.process_start_time;
+    if (startedAt) {
+      const tick = () => setActivityTime(Math.max(0, Math.floor(Date.now() / 1000) - startedAt));
+      tick();
+      const interval = window.setInterval(tick, 1000);
+      return () => clearInterval(interval);
+    }
+
     const interval = window.setInterval(() => setActivityTime((prev) => prev + 1), 1000);
     return () => clearInterval(interval);
   }, [activityWindow, activityPhase]);
@@ -78,87 +98,83 @@ function App() {
     return `${minutes}:${secs.toString().padStart(2, '0')}`;
   }, []);
 
-  // Load allowed processes config once on mount
-  useEffect(() => {
-    (async () => {
-      try {
-        const apps = await invoke<AppConfigItem[]>("get_allowed_processes");
-        if (Array.isArray(apps)) {
-          setAppsConfig(apps);
-          console.log('Loaded apps config:', apps.length, 'apps');
-        } else {
-          console.warn('get_allowed_processes returned non-array');
-          setAppsConfig([]);
-        }
-      } catch (e) {
-        console.warn('get_allowed_processes failed:', e);
-        setAppsConfig([]);
-      }
-    })();
-  }, []);
-
-  // RPC lifecycle cleanup
+  // Live-статус Discord RPC из событий бэкенда
   useEffect(() => {
+    const unsubscribe = subscribeRpcEvents({
+      "discord-rpc:connected": (e) => console.log("Discord RPC connected:", e.endpoint),
+      "discord-rpc:ready": (e) => {
+        console.log("Discord user:", e.user.global_name ?? e.user.username);
+        setDiscordUser(e.user);
+      },
+      "discord-rpc:disconnected": (e) => {
+        if (e.error) console.warn("Discord RPC disconnected:", formatRpcError(e.error));
+      },
+      "discord-rpc:error": (e) => console.error("Discord RPC error:", formatRpcError(e)),
+    });
     return () => {
-      invoke("close_rpc").catch(() => {});
-      currentAppIdRef.current = null;
+      unsubscribe.then((unlisten) => unlisten());
     };
   }, []);
 
-  // Memoize app config lookup
-  const getAppConfig = useCallback((processName: string) => {
-    return appsConfig.find(a => a.process_name.toLowerCase() === processName.toLowerCase());
-  }, [appsConfig]);
+  // Профиль из Discord: пользователь, известный бэкенду после init_rpc
+  useEffect(() => {
+    invoke<DiscordUser | null>("get_discord_user")
+      .then((user) => user && setDiscordUser(user))
+      .catch(() => {});
+  }, []);
 
-  // Update RPC when activity window changes
   useEffect(() => {
-    const updateRpc = async () => {
-      try {
-        if (!activityWindow) {
-          await invoke("clear_rpc").catch(() => {});
-          console.log("✅ RPC cleared");
-          return;
-        }
-
-        // Initialize RPC if needed
-        if (!currentAppIdRef.current) {
-          const match = getAppConfig(activityWindow.process_name);
-          if (match?.app_id) {
-            try {
-              await invoke("init_rpc", { appId: match.app_id });
-              currentAppIdRef.current = match.app_id;
-              console.log("✅ RPC initialized:", match.display_name);
-            } catch (e) {
-              console.error("Failed to init RPC:", e);
-              return;
-            }
-          } else {
-            console.warn("No app_id for:", activityWindow.process_name);
-            return;
-          }
-        }
-
-        const stateText = activityWindow.document_name || activityWindow.title || "";
-        const sendType = config?.settings?.activityType ?? activityType;
-
-        await invoke("update_rpc", {
-          details: "",
-          stateText,
-          largeImage: "appicon",
-          smallImage: "fileicon",
-          largeText: "",
-          smallText: "",
-          activityType: sendType
-        });
-
-        console.log("✅ RPC updated:", activityWindow.display_name);
-      } catch (err) {
-        console.error("❌ RPC update failed:", err);
+    if (!syncFromDiscord || !discordUser) return;
+    setDisplayName(discordUser.global_name || discordUser.username);
+    setHandleName(`@${discordUser.username}`);
+    setAvatarUrl(discordAvatarUrl(discordUser));
+  }, [syncFromDiscord, discordUser]);
+
+  // Окна и выбранное окно приходят от движка активности в бэкенде
+  const applyEngineState = useCallback((state: EngineState) => {
+    setWindows(state.windows);
+    setPaused(state.paused);
+    if (state.last_check_ms) setLastCheckTime(new Date(state.last_check_ms));
+
+    const selected = state.selected;
+    const changed = (selected?.hwnd ?? null) !== selectedHwndRef.current;
+    selectedHwndRef.current = selected?.hwnd ?? null;
+    setSelectedWindow(selected);
+    if (!selected) {
+      if (changed) {
+        setActivityWindow(null);
+        setActivityPhase((phase) => (phase === 'out' ? phase : 'idle'));
       }
+      return;
+    }
+    setActivityWindow(selected);
+    if (changed) {
+      setActivityPhase('inHeight');
+      setTimeout(() => setActivityPhase('inCard'), 100);
+    }
+  }, []);
+
+  useEffect(() => {
+    const unsubscribe = subscribeEngineState(applyEngineState);
+    return () => {
+      unsubscribe.then((unlisten) => unlisten());
     };
+  }, [applyEngineState]);
 
-    updateRpc();
-  }, [activityWindow, config, activityType, getAppConfig]);
+  // Настройки из config.json — движку (после загрузки конфига, чтобы не отправить значения по умолчанию)
+  useEffect(() => {
+    if (!config) return;
+    invoke("configure_presence", {
+      settings: {
+        poll_interval_ms: isAutoChecking ? autoCheckInterval : 0,
+        activity_type: activityType,
+        instance: discordInstance,
+        concurrent: concurrentPresences,
+        selection: selectionPolicy,
+        profile: { display_name: displayName, handle: handleName }
+      }
+    }).catch((e) => console.error("configure_presence failed:", formatRpcError(e)));
+  }, [config, isAutoChecking, autoCheckInterval, activityType, discordInstance, concurrentPresences, selectionPolicy, displayName, handleName]);
 
   // Load config on startup
   useEffect(() => {
@@ -169,9 +185,14 @@ function App() {
         setDisplayName(cfg.profile.displayName);
         setHandleName(cfg.profile.handleName);
         setRoles(cfg.profile.roles);
+        setSyncFromDiscord(cfg.profile.syncFromDiscord ?? false);
+        setAvatarUrl(cfg.profile.avatarUrl);
         setAutoCheckInterval(cfg.settings.autoCheckInterval);
         setIsAutoChecking(cfg.settings.isAutoChecking);
         setActivityType(cfg.settings.activityType ?? 'playing');
+        setDiscordInstance(cfg.settings.discordInstance ?? { kind: "any" });
+        setConcurrentPresences(cfg.settings.concurrentPresences ?? false);
+        setSelectionPolicy(cfg.settings.selectionPolicy ?? { kind: "first" });
       } catch (e) {
         console.error("Failed to load config:", e);
       }
@@ -190,8 +211,8 @@ function App() {
     // Debounce save by 1 second
     saveTimeoutRef.current = setTimeout(() => {
       const cfgToSave: Config = {
-        profile: { displayName, handleName, roles },
-        settings: { autoCheckInterval, isAutoChecking, activityType }
+        profile: { displayName, handleName, roles, syncFromDiscord, avatarUrl },
+        settings: { autoCheckInterval, isAutoChecking, activityType, discordInstance, concurrentPresences, selectionPolicy }
       };
       
       saveConfig(cfgToSave).catch((e) => console.warn("Autosave failed:", e));
@@ -203,112 +224,34 @@ function App() {
         clearTimeout(saveTimeoutRef.current);
       }
     };
-  }, [displayName, handleName, roles, autoCheckInterval, isAutoChecking, activityType, config]);
-
-  const loadWindows = useCallback(async () => {
-    try {
-      const windowsList = await invoke<WindowInfo[]>('get_windows_list');
-      const filtered = windowsList.filter((w) => w.document_name?.trim());
-      setWindows(filtered);
-      setLastCheckTime(new Date());
-
-      // Auto-select first window if none selected
-      setSelectedWindow((prev) => {
-        if (!prev && filtered.length > 0) {
-          const first = filtered[0];
-          setActivityWindow(first);
-          setActivityPhase('inHeight');
-          setTimeout(() => setActivityPhase('inCard'), 100);
-          return first;
-        }
-        return prev;
-      });
-    } catch (error) {
-      console.error('Failed to get windows:', error);
-    }
-  }, []);
+  }, [displayName, handleName, roles, syncFromDiscord, avatarUrl, autoCheckInterval, isAutoChecking, activityType, discordInstance, concurrentPresences, selectionPolicy, config]);
 
   const applyActivityType = useCallback(async () => {
     try {
       const cfgToSave: Config = {
-        profile: { displayName, handleName, roles },
-        settings: { autoCheckInterval, isAutoChecking, activityType }
+        profile: { displayName, handleName, roles, syncFromDiscord, avatarUrl },
+        settings: { autoCheckInterval, isAutoChecking, activityType, discordInstance, concurrentPresences, selectionPolicy }
       };
 
       await saveConfig(cfgToSave);
       setConfig(cfgToSave);
-
-      if (!activityWindow) {
-        console.warn("No activityWindow — saved but skipping RPC update");
-        return;
-      }
-
-      const stateText = activityWindow.document_name || activityWindow.title || "";
-
-      await invoke("update_rpc", {
-        details: "",
-        stateText,
-        largeImage: "appicon",
-        smallImage: "fileicon",
-        largeText: "",
-        smallText: "",
-        activityType: cfgToSave.settings.activityType
-      });
-
+      // Новый тип активности движок получит через configure_presence
       console.log("Applied activityType:", cfgToSave.settings.activityType);
     } catch (err) {
       console.error("applyActivityType failed:", err);
       throw err;
     }
-  }, [activityType, activityWindow, autoCheckInterval, displayName, handleName, isAutoChecking, roles]);
-
-  const checkWindowsStatus = useCallback(async () => {
-    if (windows.length === 0) {
-      setLastCheckTime(new Date());
-      return;
-    }
-
-    const validationPromises = windows.map(async (win) => {
-      try {
-        const isActive = await invoke<boolean>('is_window_active', { hwnd: parseInt(win.hwnd) });
-        return isActive ? win : null;
-      } catch {
-        return win; // Keep window if check fails
-      }
-    });
-
-    const results = await Promise.all(validationPromises);
-    const updatedWindows = results.filter((w): w is WindowInfo => w !== null);
-    
-    const hasChanges = updatedWindows.length !== windows.length;
-    setLastCheckTime(new Date());
-
-    if (hasChanges) {
-      setWindows(updatedWindows);
-      
-      // Clear selections if window was removed
-      if (selectedWindow && !updatedWindows.find(w => w.hwnd === selectedWindow.hwnd)) {
-        setSelectedWindow(null);
-      }
-      if (activityWindow && !updatedWindows.find(w => w.hwnd === activityWindow.hwnd)) {
-        setActivityWindow(null);
-        setActivityPhase('idle');
-      }
-    }
-  }, [windows, selectedWindow, activityWindow]);
+  }, [activityType, autoCheckInterval, displayName, handleName, isAutoChecking, roles, syncFromDiscord, avatarUrl, discordInstance, concurrentPresences, selectionPolicy]);
 
   const manualCheckWindows = async () => {
     setLoading(true);
     const startTime = Date.now();
 
     try {
-      await checkWindowsStatus();
-      await loadWindows();
+      applyEngineState(await invoke<EngineState>("refresh_windows"));
     } catch (error) {
-      console.error('Manual check failed:', error);
+      console.error('Manual check failed:', formatRpcError(error));
     } finally {
-      setLastCheckTime(new Date());
-
       const elapsed = Date.now() - startTime;
       const minDelay = 1000;
       if (elapsed < minDelay) {
@@ -319,77 +262,19 @@ function App() {
     }
   };
 
-  // Auto-check interval setup
-  useEffect(() => {
-    if (autoCheckTimerRef.current) {
-      clearInterval(autoCheckTimerRef.current);
-      autoCheckTimerRef.current = null;
-    }
-
-    if (!isAutoChecking || !autoCheckInterval || autoCheckInterval <= 0) {
-      return;
-    }
-
-    const run = async () => {
-      try {
-        await checkWindowsStatus();
-        await loadWindows();
-      } catch (e) {
-        console.error("Auto-check failed:", e);
-      } finally {
-        setLastCheckTime(new Date());
-      }
-    };
-
-    autoCheckTimerRef.current = window.setInterval(run, autoCheckInterval);
-
-    return () => {
-      if (autoCheckTimerRef.current) {
-        clearInterval(autoCheckTimerRef.current);
-        autoCheckTimerRef.current = null;
-      }
-    };
-  }, [isAutoChecking, autoCheckInterval, checkWindowsStatus, loadWindows]);
-
-  useEffect(() => { loadWindows(); }, [loadWindows]);
-
   const onWindowClick = async (window: WindowInfo) => {
     try {
       if (selectedWindow?.hwnd === window.hwnd) {
-        // Deselect
-        setSelectedWindow(null);
-        setActivityWindow(null);
+        // Deselect — движок очистит активность
         setActivityPhase('out');
         setActivityTime(0);
-        await invoke("clear_rpc").catch(() => {});
-        await invoke("close_rpc").catch(() => {});
-        currentAppIdRef.current = null;
+        await invoke("select_window", { hwnd: null });
       } else {
-        // Select new window
-        setSelectedWindow(window);
-        setActivityWindow(window);
         setActivityTime(0);
-        setActivityPhase('inHeight');
-        setTimeout(() => setActivityPhase('inCard'), 100);
-
-        const match = getAppConfig(window.process_name);
-        if (match?.app_id) {
-          if (currentAppIdRef.current !== match.app_id) {
-            await invoke("close_rpc").catch(() => {});
-            try {
-              await invoke("init_rpc", { appId: match.app_id });
-              currentAppIdRef.current = match.app_id;
-              console.log("RPC initialized:", match.display_name);
-            } catch (e) {
-              console.error("init_rpc failed:", e);
-            }
-          }
-        } else {
-          console.warn("No app config for:", window.process_name);
-        }
+        await invoke("select_window", { hwnd: window.hwnd });
       }
     } catch (e) {
-      console.error("Failed selecting window:", e);
+      console.error("Failed selecting window:", formatRpcError(e));
     }
   };
 
@@ -476,6 +361,9 @@ function App() {
             setDisplayName={setDisplayName}
             handleName={handleName}
             setHandleName={setHandleName}
+            avatarUrl={avatarUrl}
+            syncFromDiscord={syncFromDiscord}
+            setSyncFromDiscord={setSyncFromDiscord}
             roles={roles}
             removingRoleId={removingRoleId}
             setRemovingRoleId={setRemovingRoleId}
@@ -520,6 +408,57 @@ function App() {
                       <option value={30000}>30s</option>
                     </select>
                   </div>
+                  <label className="auto-check-status" title="Show a separate activity for every detected application">
+                    <input
+                      type="checkbox"
+                      checked={concurrentPresences}
+                      onChange={(e) => setConcurrentPresences(e.target.checked)}
+                    />
+                    <span className="status-text">All apps</span>
+                  </label>
+                  <div className="auto-check-status">
+                    <span className="status-text">Follow:</span>
+                    <select
+                      className="interval-selector"
+                      value={selectionPolicy.kind}
+                      onChange={(e) => {
+                        const kind = e.target.value as SelectionPolicy["kind"];
+                        // Приоритет — приложения в порядке, в котором сейчас найдены их окна
+                        setSelectionPolicy(kind === "priority"
+                          ? { kind, processes: [...new Set(windows.map((w) => w.process_name))] }
+                          : { kind });
+                      }}
+                    >
+                      <option value="first">First found</option>
+                      <option value="focused">Focused</option>
+                      <option value="recently_focused">Last focused</option>
+                      <option value="priority">Priority</option>
+                    </select>
+                  </div>
+                  <div className="auto-check-status">
+                    <span className="status-text">Discord:</span>
+                    <select
+                      className="interval-selector"
+                      value={instanceToValue(discordInstance)}
+                      // Сменили экземпляр — движок переподключится сам
+                      onChange={(e) => setDiscordInstance(valueToInstance(e.target.value))}
+                    >
+                      <option value="any">Any</option>
+                      <option value="build:stable">Stable</option>
+                      <option value="build:ptb">PTB</option>
+                      <option value="build:canary">Canary</option>
+                      {Array.from({ length: 10 }, (_, i) => (
+                        <option key={i} value={`index:${i}`}>discord-ipc-{i}</option>
+                      ))}
+                    </select>
+                  </div>
+                  <button
+                    onClick={() => invoke(paused ? "resume_presence" : "pause_presence").catch((e) => console.error(formatRpcError(e)))}
+                    className="btn-apps"
+                    title="Pause or resume the Discord activity"
+                  >
+                    {paused ? 'Resume' : 'Pause'}
+                  </button>
                   <button onClick={manualCheckWindows} className="btn-apps" disabled={loading}>
                     {loading ? 'Checking...' : 'Check Status'}
                   </button>        
@@ -537,10 +476,10 @@ function App() {
                   <div
                     key={`${window.hwnd}-${index}`}
                     onClick={() => onWindowClick(window)}
-                    className={`window-item ${selectedWindow?.hwnd === window.hwnd ? 'selected' : ''}`}
+                    className={`window-item ${selectedWindow?.hwnd === window.hwnd ? 'selected' : ''} ${window.focused ? 'focused' : ''}`}
                   >
                     <div className="window-header">
-                      <img src={window.icon_path} alt="App icon" className="app-icon" />
+                      <img src={iconSrc(window.icon_path)} alt="App icon" className="app-icon" />
                     </div>
                     <div className="app-info">
                       <div className="app-name">{window.display_name}</div>
@@ -7,17 +7,37 @@ export interface Role {
   color: string;
 }
 
+// Какой из запущенных Discord использовать (InstanceSelector в discord_ipc.rs)
+export type DiscordInstance =
+  | { kind: "any" }
+  | { kind: "index"; index: number }
+  | { kind: "build"; build: "stable" | "ptb" | "canary" };
+
+// Какое окно показывать, пока пользователь не закрепил окно сам (SelectionPolicy в window_focus.rs)
+export type SelectionPolicy =
+  | { kind: "first" }
+  | { kind: "focused" }
+  | { kind: "recently_focused" }
+  | { kind: "priority"; processes: string[] };
+
 export interface Config {
   profile: {
     displayName: string;
     handleName: string;
     roles: Role[];
+    // брать имя, @handle и аватар из Discord (READY) вместо ручных полей
+    syncFromDiscord?: boolean;
+    avatarUrl?: string;
   };
   settings: {
     autoCheckInterval: number;
     isAutoChecking: boolean;
     // новое поле — тип активности для RPC
     activityType?: string;
+    discordInstance?: DiscordInstance;
+    // отдельное соединение и активность для каждого найденного приложения
+    concurrentPresences?: boolean;
+    selectionPolicy?: SelectionPolicy;
   };
 }
 
@@ -25,12 +45,16 @@ const defaultConfig: Config = {
   profile: {
     displayName: "Your Name",
     handleName: "@username",
-    roles: []
+    roles: [],
+    syncFromDiscord: true
   },
   settings: {
     autoCheckInterval: 5000,
     isAutoChecking: true,
-    activityType: "playing"
+    activityType: "playing",
+    discordInstance: { kind: "any" },
+    concurrentPresences: false,
+    selectionPolicy: { kind: "first" }
   }
 };
 
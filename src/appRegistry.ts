@@ -0,0 +1,72 @@
+// Реестр приложений (src-tauri/src/app_registry.rs): встроенный список плюс apps.json пользователя
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
+
+export interface AllowedProcess {
+  process_name: string;
+  icon_path: string;
+  display_name: string;
+  app_id?: string | null;
+  title_extract_patterns?: string[] | null;
+  presence?: PresenceTemplate | null;
+}
+
+// Шаблоны текста активности (src-tauri/src/presence_template.rs): "Editing {file}",
+// "{file|document|title}", '{version|"latest"}'. Переменные: app, display_name, process_name,
+// title, document, elapsed, user, handle и именованные группы шаблона заголовка
+export interface PresenceTemplate {
+  details?: string | null;
+  state?: string | null;
+  large_image?: string | null;
+  large_text?: string | null;
+  small_image?: string | null;
+  small_text?: string | null;
+}
+
+export type AppSource = "bundled" | "user" | "override";
+
+export interface AppEntry extends AllowedProcess {
+  source: AppSource;
+}
+
+// details.fields у ошибки invalid_app
+export type AppFieldError = { field: string } & (
+  | { code: "required" }
+  | { code: "invalid_regex"; reason: string }
+  | { code: "invalid_snowflake"; value: string }
+  | { code: "icon_not_found"; path: string }
+  | { code: "invalid_template"; reason: string }
+  | { code: "already_exists" }
+  | { code: "bundled" }
+);
+
+// Адрес иконки по icon_path: свои иконки лежат в папке конфигурации рядом с apps.json,
+// встроенные — в ресурсах; обе отдаёт схема jrpce-icon (ICON_SCHEME в app_registry.rs)
+export const iconSrc = (iconPath: string) => convertFileSrc(iconPath, "jrpce-icon");
+
+export const listApps = () => invoke<AppEntry[]>("list_apps");
+export const addApp = (app: AllowedProcess) => invoke<AppEntry[]>("add_app", { app });
+export const updateApp = (app: AllowedProcess) => invoke<AppEntry[]>("update_app", { app });
+export const removeApp = (processName: string) => invoke<AppEntry[]>("remove_app", { processName });
+
+export interface TitleMatch {
+  pattern_index: number;
+  // [0] — совпадение целиком; null — группа не участвовала
+  groups: (string | null)[];
+  fields: Record<string, string>;
+  document_name: string;
+}
+
+export interface TitleTest {
+  title: string;
+  // процесс открытого окна; null — заголовок из sampleTitles
+  process_name: string | null;
+  matched: TitleMatch | null;
+}
+
+/** Проверить шаблоны заголовка; includeOpenWindows добавляет открытые окна (processName — только этого процесса) */
+export const testTitlePatterns = (
+  patterns: string[],
+  sampleTitles: string[],
+  includeOpenWindows = false,
+  processName: string | null = null
+) => invoke<TitleTest[]>("test_title_patterns", { patterns, sampleTitles, includeOpenWindows, processName });
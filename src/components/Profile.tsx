@@ -1,8 +1,9 @@
 // src/components/Profile.tsx
 import React, { useEffect, useRef, useState } from 'react';
+import { iconSrc } from '../appRegistry';
 
 interface WindowInfo {
-  hwnd: string;
+  hwnd: number;
   title: string;
   process_name: string;
   icon_path: string;
@@ -15,6 +16,10 @@ interface ProfileProps {
   setDisplayName: (name: string) => void;
   handleName: string;
   setHandleName: (name: string) => void;
+  avatarUrl?: string;
+  // имя и @handle приходят из Discord и не редактируются вручную
+  syncFromDiscord: boolean;
+  setSyncFromDiscord: (sync: boolean) => void;
   roles: Array<{ id: string; name: string; color: string }>;
   removingRoleId: string | null;
   setRemovingRoleId: (id: string | null) => void;
@@ -37,6 +42,9 @@ export const Profile: React.FC<ProfileProps> = ({
   setDisplayName,
   handleName,
   setHandleName,
+  avatarUrl,
+  syncFromDiscord,
+  setSyncFromDiscord,
   roles,
   removingRoleId,
   setRemovingRoleId,
@@ -100,14 +108,14 @@ export const Profile: React.FC<ProfileProps> = ({
         <div className="profile-banner" />
         <div className="profile-card">
           <div className="avatar-wrap">
-            <img src='icons/user.png' alt="avatar" className="avatar-lg" />
+            <img src={avatarUrl || 'icons/user.png'} alt="avatar" className="avatar-lg" />
             <span className="status-dot online dot-lg" />
           </div>
 
           <div className="profile-names">
             <div
               className="display-name"
-              contentEditable
+              contentEditable={!syncFromDiscord}
               suppressContentEditableWarning
               spellCheck={false}
               onBlur={(e) => {
@@ -120,7 +128,7 @@ export const Profile: React.FC<ProfileProps> = ({
             <div className="handle-row">
               <span
                 className="handle"
-                contentEditable
+                contentEditable={!syncFromDiscord}
                 suppressContentEditableWarning
                 spellCheck={false}
                 onBlur={(e) => {
@@ -131,6 +139,14 @@ export const Profile: React.FC<ProfileProps> = ({
                 {handleName}
               </span>
             </div>
+            <label className="discord-sync" title="Use the name and avatar of the logged-in Discord user">
+              <input
+                type="checkbox"
+                checked={syncFromDiscord}
+                onChange={(e) => setSyncFromDiscord(e.target.checked)}
+              />
+              Sync with Discord
+            </label>
           </div>
 
           {/* Игровая активность */}
@@ -182,7 +198,7 @@ export const Profile: React.FC<ProfileProps> = ({
                 )}
 
                 <div className='game-playing'>
-                  <img src={activityWindow.icon_path} alt="app" className="game-icon" />
+                  <img src={iconSrc(activityWindow.icon_path)} alt="app" className="game-icon" />
                   <div className="game-lines">
                     <div className='game-text'>
                       <div className="game-name">{activityWindow.display_name}</div>
@@ -0,0 +1,48 @@
+// Движок активности в бэкенде (src-tauri/src/presence_engine.rs): опрос окон и обновление RPC
+import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import { DiscordInstance, SelectionPolicy } from "./Config";
+
+export interface WindowInfo {
+  hwnd: number;
+  title: string;
+  process_name: string;
+  pid?: number | null;
+  icon_path: string;
+  display_name: string;
+  document_name: string;
+  // именованные группы шаблона заголовка: (?P<version>…), (?P<file>…)
+  fields: Record<string, string>;
+  process_start_time?: number | null;
+  focused: boolean;
+  focus_time_ms: number;
+  last_focused_ms: number | null;
+}
+
+export interface EngineSettings {
+  // 0 — опрос только по refresh_windows
+  poll_interval_ms: number;
+  activity_type: string;
+  instance: DiscordInstance;
+  concurrent: boolean;
+  selection: SelectionPolicy;
+  // для {user} и {handle} в шаблонах активности; пустые — из Discord
+  profile: { display_name: string; handle: string };
+}
+
+export interface EngineState {
+  windows: WindowInfo[];
+  selected: WindowInfo | null;
+  // окно в фокусе — любое, не только из windows
+  focused: number | null;
+  paused: boolean;
+  last_check_ms: number | null;
+  settings: EngineSettings;
+}
+
+/** Текущее состояние и подписка на его изменения; возвращает функцию отписки */
+export async function subscribeEngineState(onState: (state: EngineState) => void): Promise<UnlistenFn> {
+  const unlisten = await listen<EngineState>("presence-engine:state", (event) => onState(event.payload));
+  onState(await invoke<EngineState>("get_presence_state"));
+  return unlisten;
+}
@@ -0,0 +1,58 @@
+// Ошибки команд бэкенда (src-tauri/src/error.rs): код и подсказки стабильны, текст подбираем здесь
+
+export type RpcErrorCode =
+  | "discord_not_running"
+  | "instance_not_found"
+  | "invalid_app_id"
+  | "not_initialized"
+  | "connection_lost"
+  | "discord_rejected"
+  | "protocol_error"
+  | "invalid_presence"
+  | "worker_unavailable"
+  | "window_not_found"
+  | "window_operation_failed"
+  | "invalid_app"
+  | "app_not_found"
+  | "internal";
+
+export type RpcHint =
+  | "start_discord"
+  | "use_desktop_client"
+  | "check_app_id"
+  | "wait_for_discord"
+  | "run_debug_ipc_pipes"
+  | "call_init_rpc"
+  | "check_instance_setting"
+  | "restart_app";
+
+export interface RpcError {
+  code: RpcErrorCode;
+  message: string;
+  hints?: RpcHint[];
+  details?: unknown;
+}
+
+const HINT_TEXT: Record<RpcHint, string> = {
+  start_discord: "Discord Desktop запущен",
+  use_desktop_client: "Используется Discord Desktop (не Microsoft Store и не браузер)",
+  check_app_id: "App ID верен",
+  wait_for_discord: "Discord полностью загрузился и не в состоянии обновления",
+  run_debug_ipc_pipes: "Посмотрите вывод debug_ipc_pipes и логи Discord",
+  call_init_rpc: "RPC инициализирован (init_rpc)",
+  check_instance_setting: "В настройках выбран запущенный экземпляр Discord",
+  restart_app: "Перезапустите приложение",
+};
+
+export function isRpcError(err: unknown): err is RpcError {
+  return typeof err === "object" && err !== null && "code" in err && "message" in err;
+}
+
+/** Сообщение об ошибке с подсказками на русском */
+export function formatRpcError(err: unknown): string {
+  if (!isRpcError(err)) return String(err);
+  const hints = (err.hints ?? []).map((h, i) => ` ${i + 1}) ${HINT_TEXT[h] ?? h}`);
+  return hints.length > 0
+    ? `${err.message}\nПроверьте, что:\n${hints.join("\n")}`
+    : err.message;
+}
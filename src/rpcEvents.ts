@@ -0,0 +1,48 @@
+// События Discord RPC из бэкенда (src-tauri/src/rpc_events.rs)
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import { RpcError } from "./rpcError";
+
+export interface DiscordUser {
+  id: string;
+  username: string;
+  discriminator: string;
+  global_name: string | null;
+  avatar: string | null;
+}
+
+export interface ReadyData {
+  v: number;
+  config: { cdn_host: string; api_endpoint: string; environment: string };
+  user: DiscordUser;
+}
+
+export interface RpcEventMap {
+  "discord-rpc:connected": { app_id: string; endpoint: string | null; reconnect_attempts: number };
+  "discord-rpc:disconnected": { app_id: string | null; error: RpcError | null };
+  "discord-rpc:ready": ReadyData & { app_id: string };
+  "discord-rpc:error": RpcError & { app_id: string | null };
+  "discord-rpc:activity": { app_id: string | null; activity: unknown };
+}
+
+export type RpcEventHandlers = {
+  [K in keyof RpcEventMap]?: (payload: RpcEventMap[K]) => void;
+};
+
+/** Подписаться на события RPC; возвращает функцию отписки от всех */
+export async function subscribeRpcEvents(handlers: RpcEventHandlers): Promise<UnlistenFn> {
+  const unlisteners = await Promise.all(
+    (Object.keys(handlers) as (keyof RpcEventMap)[]).map((name) =>
+      listen<never>(name, (event) => (handlers[name] as (p: unknown) => void)(event.payload))
+    )
+  );
+  return () => unlisteners.forEach((unlisten) => unlisten());
+}
+
+/** URL аватара на CDN Discord; без своего аватара — стандартный по id */
+export function discordAvatarUrl(user: DiscordUser): string {
+  if (user.avatar) {
+    return `https://cdn.discordapp.com/avatars/${user.id}/${user.avatar}.png?size=128`;
+  }
+  const index = Number((BigInt(user.id) >> 22n) % 6n);
+  return `https://cdn.discordapp.com/embed/avatars/${index}.png`;
+}